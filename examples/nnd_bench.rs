@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+use succparen::bitwise::ops::{self, ExternalByteSize, PredOne, RankBit, SelectOne, SuccOne};
+use succparen::bitwise::{Index, SparseOneNnd};
+use succparen::storage::Owned;
+
+fn main() -> noargs::Result<()> {
+    let mut args = noargs::raw_args();
+    args.metadata_mut().app_name = "nnd_bench";
+
+    if noargs::VERSION_FLAG.take(&mut args).is_present() {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    noargs::HELP_FLAG.take_help(&mut args);
+
+    let len: Index = noargs::opt("len")
+        .short('l')
+        .default("10000000")
+        .take(&mut args)
+        .then(|a| a.value().parse())?;
+    let interval: Index = noargs::opt("interval")
+        .short('i')
+        .doc("Distance between adjacent one bits")
+        .default("300")
+        .take(&mut args)
+        .then(|a| a.value().parse())?;
+    let queries: Index = noargs::opt("queries")
+        .short('q')
+        .default("1000000")
+        .take(&mut args)
+        .then(|a| a.value().parse())?;
+
+    if let Some(help) = args.finish()? {
+        print!("{help}");
+        return Ok(());
+    }
+
+    let nnd = (0..len)
+        .map(|i| i % interval == 0)
+        .collect::<SparseOneNnd>();
+    let indices = (0..queries)
+        .map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) % len)
+        .collect::<Vec<_>>();
+
+    bench("pred_one", &indices, |i| nnd.pred_one(i));
+    bench("naive_pred_one", &indices, |i| ops::naive_pred_one(&nnd, i));
+    bench("succ_one", &indices, |i| nnd.succ_one(i));
+    bench("naive_succ_one", &indices, |i| ops::naive_succ_one(&nnd, i));
+
+    // Space and time for several block sizes (`SMALL_SIZE`, `MIDDLE_SMALLS`, `LARGE_MIDDLES`).
+    println!();
+    block_sizes::<64, 4, 16>(len, interval, &indices);
+    block_sizes::<128, 8, 32>(len, interval, &indices);
+    block_sizes::<256, 8, 32>(len, interval, &indices);
+    block_sizes::<256, 32, 8>(len, interval, &indices);
+    Ok(())
+}
+
+fn block_sizes<const S: usize, const M: usize, const L: usize>(
+    len: Index,
+    interval: Index,
+    indices: &[Index],
+) {
+    let nnd = (0..len)
+        .map(|i| i % interval == 0)
+        .collect::<SparseOneNnd<Owned, S, M, L>>();
+    let ones = nnd.count_ones().max(1);
+    println!(
+        "# SparseOneNnd<{S}, {M}, {L}>: {} bytes",
+        nnd.external_byte_size()
+    );
+    bench("rank_one", indices, |i| Some(nnd.rank_one(i)));
+    bench("select_one", indices, |i| nnd.select_one(i % ones + 1));
+}
+
+fn bench<F>(name: &str, indices: &[Index], f: F)
+where
+    F: Fn(Index) -> Option<Index>,
+{
+    let start = Instant::now();
+    let mut sum: Index = 0;
+    for &i in indices {
+        sum = sum.wrapping_add(f(i).unwrap_or(0));
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name}: {:?} ({:.1} ns/op, checksum={sum})",
+        elapsed,
+        per_op_nanos(elapsed, indices.len())
+    );
+}
+
+fn per_op_nanos(elapsed: Duration, count: usize) -> f64 {
+    elapsed.as_nanos() as f64 / count.max(1) as f64
+}
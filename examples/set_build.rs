@@ -18,12 +18,14 @@ fn main() -> noargs::Result<()> {
 
     let ty = noargs::opt("type")
         .short('t')
-        .ty("null | hashset | splayset | parentheses")
+        .ty("null | hashset | splayset | parentheses | louds | dfuds")
         .example("hashset")
         .take(&mut args)
         .then(|a| match a.value() {
-            "null" | "hashset" | "splayset" | "parentheses" => Ok(a.value().to_owned()),
-            _ => Err("type must be one of: null, hashset, splayset, parentheses"),
+            "null" | "hashset" | "splayset" | "parentheses" | "louds" | "dfuds" => {
+                Ok(a.value().to_owned())
+            }
+            _ => Err("type must be one of: null, hashset, splayset, parentheses, louds, dfuds"),
         })?;
     let show_words = noargs::flag("show_words")
         .short('w')
@@ -59,6 +61,32 @@ fn main() -> noargs::Result<()> {
                 }
             }
         }
+        "louds" => {
+            let lines = traversal::ByteLines::new(stdin.lock()).into_depth_first_traversal();
+            let tree =
+                succparen::tree::louds::LoudsTree::<_>::new_builder(lines, word::Letters::new())
+                    .build_all();
+            println!("NODES: {}", tree.len());
+            println!("BYTES: {}", tree.external_byte_size());
+            if show_words {
+                for word in word::Words::new(tree.root()) {
+                    println!("{}", String::from_utf8(word).unwrap());
+                }
+            }
+        }
+        "dfuds" => {
+            let lines = traversal::ByteLines::new(stdin.lock()).into_depth_first_traversal();
+            let tree =
+                succparen::tree::dfuds::DfudsTree::<_>::new_builder(lines, word::Letters::new())
+                    .build_all();
+            println!("NODES: {}", tree.len());
+            println!("BYTES: {}", tree.external_byte_size());
+            if show_words {
+                for word in word::Words::new(tree.root()) {
+                    println!("{}", String::from_utf8(word).unwrap());
+                }
+            }
+        }
         _ => unreachable!(),
     }
     Ok(())
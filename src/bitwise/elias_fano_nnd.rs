@@ -0,0 +1,298 @@
+use std::io::{self, Read, Write};
+use std::iter;
+use std::mem;
+
+use super::ops;
+use super::ops::{PredOne, RankBit, SelectOne, SelectZero, SuccOne};
+use super::{Bit, BitString, Index, IndexedBitString, Rank};
+use crate::serialize::{self, FromBytes, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
+
+const MAGIC: &[u8; 4] = b"EFNN";
+
+/// Elias-Fano encoding of the indices of the one bits.
+///
+/// The lower `low_width` bits of each index are packed into `lows`, and the upper bits are
+/// stored in `highs` in unary (the `i`-th index sets the bit at `(index >> low_width) + i`).
+#[derive(Debug, Clone)]
+pub struct EliasFanoNnd<S: Storage = Owned> {
+    len: Index,
+    low_width: u64,
+    lows: S::Slice<u64>,
+    highs: IndexedBitString<S>,
+}
+impl EliasFanoNnd {
+    fn from_one_indices<I>(len: Index, count: Rank, ones: I) -> Self
+    where
+        I: Iterator<Item = Index>,
+    {
+        let ratio = len.checked_div(count).unwrap_or(len);
+        let low_width = if ratio <= 1 {
+            0
+        } else {
+            63 - ratio.leading_zeros() as u64
+        };
+
+        let mut lows = vec![0; (count * low_width).div_ceil(64) as usize];
+        let mut highs = BitString::new();
+        highs.resize(count + (len >> low_width) + 1);
+        for (i, index) in ones.enumerate() {
+            debug_assert!(index < len);
+            if low_width > 0 {
+                let low = index & low_mask(low_width);
+                let position = i as u64 * low_width;
+                let (word, offset) = ((position / 64) as usize, position % 64);
+                lows[word] |= low << offset;
+                if offset + low_width > 64 {
+                    lows[word + 1] |= low >> (64 - offset);
+                }
+            }
+            highs.set((index >> low_width) + i as Index, true);
+        }
+        EliasFanoNnd {
+            len,
+            low_width,
+            lows,
+            highs: IndexedBitString::from(highs),
+        }
+    }
+}
+impl<S: Storage> EliasFanoNnd<S> {
+    pub fn len(&self) -> Index {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn count_ones(&self) -> Rank {
+        self.highs.count_ones()
+    }
+
+    fn low(&self, i: Rank) -> Index {
+        if self.low_width == 0 {
+            return 0;
+        }
+        let position = i * self.low_width;
+        let (word, offset) = ((position / 64) as usize, position % 64);
+        let mut low = self.lows[word] >> offset;
+        if offset + self.low_width > 64 {
+            low |= self.lows[word + 1] << (64 - offset);
+        }
+        low & low_mask(self.low_width)
+    }
+
+    // Returns the position in `highs` where the bucket of the upper bits of `index` starts
+    // and the number of the ones before the bucket.
+    fn bucket(&self, index: Index) -> (Index, Rank) {
+        // The ones whose upper bits are `high` follow the `high`-th zero of `highs`.
+        let high = index >> self.low_width;
+        let position = match high {
+            0 => 0,
+            _ => self.highs.select_zero(high).unwrap() + 1,
+        };
+        (position, position - high)
+    }
+
+    // Returns the index of the `rank`-th (from 0) one, which is at `position` in `highs`.
+    fn one_at(&self, position: Index, rank: Rank) -> Index {
+        ((position - rank) << self.low_width) | self.low(rank)
+    }
+}
+impl iter::FromIterator<Bit> for EliasFanoNnd {
+    fn from_iter<I>(bits: I) -> Self
+    where
+        I: IntoIterator<Item = Bit>,
+    {
+        Self::from(bits.into_iter().collect::<BitString>())
+    }
+}
+impl From<BitString> for EliasFanoNnd {
+    fn from(bits: BitString) -> Self {
+        let count = bits
+            .as_fixnums()
+            .iter()
+            .map(|f| f.pop_count() as Rank)
+            .sum();
+        Self::from_one_indices(bits.len(), count, bits.one_indices())
+    }
+}
+impl<S: Storage> RankBit for EliasFanoNnd<S> {
+    fn rank_one(&self, index: Index) -> Rank {
+        if index >= self.len {
+            return self.count_ones();
+        }
+        let (mut position, mut rank) = self.bucket(index);
+        let low = index & low_mask(self.low_width);
+        while self.highs.get(position) == Some(true) && self.low(rank) <= low {
+            position += 1;
+            rank += 1;
+        }
+        rank
+    }
+}
+impl<S: Storage> SelectOne for EliasFanoNnd<S> {
+    fn select_one(&self, rank: Rank) -> Option<Index> {
+        let high = self.highs.select_one(rank)? - (rank - 1);
+        Some((high << self.low_width) | self.low(rank - 1))
+    }
+}
+impl<S: Storage> PredOne for EliasFanoNnd<S> {
+    fn pred_one(&self, index: Index) -> Option<Index> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = index.min(self.len - 1);
+        let (mut position, mut rank) = self.bucket(index);
+        let start = position;
+        let low = index & low_mask(self.low_width);
+        while self.highs.get(position) == Some(true) && self.low(rank) <= low {
+            position += 1;
+            rank += 1;
+        }
+        if position > start {
+            Some(self.one_at(position - 1, rank - 1))
+        } else if rank > 0 {
+            // The last one of a preceding bucket.
+            let position = self.highs.pred_one(start - 1).unwrap();
+            Some(self.one_at(position, rank - 1))
+        } else {
+            None
+        }
+    }
+}
+impl<S: Storage> SuccOne for EliasFanoNnd<S> {
+    fn succ_one(&self, index: Index) -> Option<Index> {
+        if index >= self.len {
+            return None;
+        }
+        let (mut position, mut rank) = self.bucket(index);
+        let low = index & low_mask(self.low_width);
+        while self.highs.get(position) == Some(true) && self.low(rank) < low {
+            position += 1;
+            rank += 1;
+        }
+        // The first one at or after `position` (possibly in a following bucket).
+        let position = self.highs.succ_one(position)?;
+        Some(self.one_at(position, rank))
+    }
+}
+impl<S: Storage> ops::ExternalByteSize for EliasFanoNnd<S> {
+    fn external_byte_size(&self) -> u64 {
+        mem::size_of_val(&self.len) as u64
+            + mem::size_of_val(&self.low_width) as u64
+            + self.lows.len() as u64 * mem::size_of::<u64>() as u64
+            + self.highs.external_byte_size()
+    }
+}
+
+impl<S: Storage> WriteTo for EliasFanoNnd<S> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, MAGIC)?;
+        serialize::write_u64(writer, self.len)?;
+        serialize::write_u64(writer, self.low_width)?;
+        serialize::write_slice(writer, &self.lows)?;
+        self.highs.write_to(writer)
+    }
+}
+impl ReadFrom for EliasFanoNnd {
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, MAGIC)?;
+        EliasFanoNnd {
+            len: serialize::read_u64(&mut reader)?,
+            low_width: serialize::read_u64(&mut reader)?,
+            lows: serialize::read_vec(&mut reader)?,
+            highs: IndexedBitString::read_from(&mut reader)?,
+        }
+        .validate()
+    }
+}
+impl<'a> FromBytes<'a> for EliasFanoNnd<Borrowed<'a>> {
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, MAGIC)?;
+        EliasFanoNnd {
+            len: serialize::read_u64(bytes)?,
+            low_width: serialize::read_u64(bytes)?,
+            lows: serialize::view_slice(bytes)?,
+            highs: IndexedBitString::from_bytes(bytes)?,
+        }
+        .validate()
+    }
+}
+impl<S: Storage> EliasFanoNnd<S> {
+    fn validate(self) -> io::Result<Self> {
+        let count = self.highs.count_ones();
+        if self.low_width >= 64
+            || self.lows.len() as u64 != (count * self.low_width).div_ceil(64)
+            || self.highs.len() != count + (self.len >> self.low_width) + 1
+        {
+            return Err(serialize::invalid_data(format!(
+                "Inconsistent Elias-Fano parameters: len={}, low_width={}, ones={count}",
+                self.len, self.low_width
+            )));
+        }
+        Ok(self)
+    }
+}
+
+fn low_mask(width: u64) -> u64 {
+    (1 << width) - 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::ops::*;
+    use super::super::{Bit, Index, Rank};
+    use super::*;
+    use crate::serialize::{FromBytes, ReadFrom, WriteTo};
+    use crate::storage::Borrowed;
+
+    fn check(bits: &[Bit]) {
+        let expected = LinearFid::new(bits.iter().cloned());
+        let nnd = bits.iter().cloned().collect::<EliasFanoNnd>();
+        for i in 0..bits.len() as Index + 2 {
+            assert_eq!(nnd.rank_one(i), expected.rank_one(i), "rank_one({i})");
+            assert_eq!(
+                nnd.select_one(i + 1),
+                expected.select_one(i + 1),
+                "select_one({})",
+                i + 1
+            );
+            assert_eq!(nnd.pred_one(i), expected.pred_one(i), "pred_one({i})");
+            assert_eq!(nnd.succ_one(i), expected.succ_one(i), "succ_one({i})");
+        }
+        assert_eq!(nnd.select_one(0), None);
+    }
+
+    #[test]
+    fn it_works() {
+        check(&(0..1024).map(|i| i % 5 == 0).collect::<Vec<_>>());
+        check(&(0..1000).map(|i| i % 97 == 3).collect::<Vec<_>>());
+        check(&(0..300).map(|i| i % 3 != 0).collect::<Vec<_>>());
+        check(&(0..300).map(|i| (i * i) % 7 == 2).collect::<Vec<_>>());
+        check(
+            &(0..5000)
+                .map(|i| i == 7 || i == 8 || i == 4000)
+                .collect::<Vec<_>>(),
+        );
+        check(&[true; 70]);
+        check(&[false; 70]);
+        check(&[]);
+    }
+
+    #[test]
+    fn serialize() {
+        let bits = (0..10000).map(|i| Bit::from(i % 7 == 0));
+        let nnd = bits.collect::<EliasFanoNnd>();
+        let mut buf = Vec::new();
+        nnd.write_to(&mut buf).unwrap();
+
+        let read = EliasFanoNnd::read_from(&buf[..]).unwrap();
+        let aligned = serialize::aligned(&buf);
+        let view =
+            EliasFanoNnd::<Borrowed>::from_bytes(&mut serialize::aligned_bytes(&aligned)).unwrap();
+        for i in 0..10000 {
+            assert_eq!(read.rank_one(i), nnd.rank_one(i));
+            assert_eq!(view.select_one(i as Rank + 1), nnd.select_one(i + 1));
+        }
+    }
+}
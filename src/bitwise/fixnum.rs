@@ -3,16 +3,18 @@ use std::mem;
 use std::ops::{Add, BitAnd, BitOr, BitXor, Not, Shl, Shr, Sub};
 
 use super::ops::GetClose;
+use super::ops::GetOpen;
 use super::ops::RankBit;
 use super::ops::{PredOne, PredZero};
 use super::ops::{SelectOne, SelectZero};
 use super::ops::{SuccOne, SuccZero};
 use super::Bit;
 use super::{Index, Rank};
+use crate::serialize::Pod;
 
 pub trait FixnumLike
 where
-    Self: Sized + Copy + Eq + U64Like,
+    Self: Sized + Copy + Eq + U64Like + fmt::Debug + 'static,
     Self: Add<Output = Self> + Sub<Output = Self> + Not<Output = Self>,
     Self: BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self>,
     Self: Shr<Index, Output = Self> + Shl<Index, Output = Self>,
@@ -23,7 +25,7 @@ where
 }
 impl<T> FixnumLike for T
 where
-    T: Sized + Copy + Eq + U64Like,
+    T: Sized + Copy + Eq + U64Like + fmt::Debug + 'static,
     T: Add<Output = T> + Sub<Output = T> + Not<Output = T>,
     T: BitAnd<Output = T> + BitOr<Output = T> + BitXor<Output = T>,
     T: Shr<Index, Output = T> + Shl<Index, Output = T>,
@@ -31,6 +33,7 @@ where
 }
 
 #[derive(Debug, Default, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
 pub struct Fixnum<T>(T);
 impl<T> Fixnum<T>
 where
@@ -83,6 +86,14 @@ where
         (x & (T::from_u64((Self::bitwidth() as u64) << 1) - T::one())).to_u64() as usize
     }
 }
+unsafe impl<T> Pod for Fixnum<T>
+where
+    T: Pod,
+{
+    fn to_le(self) -> Self {
+        Fixnum(self.0.to_le())
+    }
+}
 impl<T> RankBit for Fixnum<T>
 where
     T: FixnumLike,
@@ -247,6 +258,25 @@ where
         None
     }
 }
+impl<T> GetOpen for Fixnum<T>
+where
+    T: FixnumLike,
+{
+    fn get_open(&self, index: Index) -> Option<Index> {
+        let mut level = 0;
+        for i in (0..=index).rev() {
+            if self.get(i) {
+                level -= 1;
+                if level == 0 {
+                    return Some(i);
+                }
+            } else {
+                level += 1;
+            }
+        }
+        None
+    }
+}
 impl<T> fmt::Display for Fixnum<T>
 where
     T: FixnumLike,
@@ -303,7 +333,9 @@ impl U64Like for u64 {
 }
 
 #[cfg(test)]
+#[allow(clippy::legacy_numeric_constants)]
 mod test {
+    use super::super::ops::{GetClose, GetOpen};
     use super::super::ops::{PredOne, PredZero, SuccOne, SuccZero};
     use super::super::ops::{RankBit, SelectOne, SelectZero};
     use super::super::{ONE, ZERO};
@@ -378,6 +410,26 @@ mod test {
         assert_eq!(f(0b101011110101000001).succ_one(30), None);
     }
 
+    #[test]
+    fn get_close_and_open() {
+        // "((()(()())" followed by close parentheses
+        let block = f(0b0010110111);
+        assert_eq!(block.get_close(0), Some(11));
+        assert_eq!(block.get_close(1), Some(10));
+        assert_eq!(block.get_close(2), Some(3));
+        assert_eq!(block.get_close(4), Some(9));
+        assert_eq!(block.get_close(5), Some(6));
+        assert_eq!(block.get_close(7), Some(8));
+
+        assert_eq!(block.get_open(3), Some(2));
+        assert_eq!(block.get_open(6), Some(5));
+        assert_eq!(block.get_open(8), Some(7));
+        assert_eq!(block.get_open(9), Some(4));
+        assert_eq!(block.get_open(10), Some(1));
+        assert_eq!(block.get_open(11), Some(0));
+        assert_eq!(block.get_open(12), None);
+    }
+
     #[test]
     fn to_string() {
         assert_eq!(
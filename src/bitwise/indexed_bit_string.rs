@@ -0,0 +1,454 @@
+use std::io::{self, Read, Write};
+use std::mem;
+
+use super::ops;
+use super::ops::{PredOne, PredZero, RankBit, SelectOne, SelectZero, SuccOne, SuccZero};
+use super::{Bit, BitString, Index, Rank};
+use crate::serialize::{self, FromBytes, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
+
+const MAGIC: &[u8; 4] = b"IBST";
+
+const BLOCK_SIZE: Index = 64;
+const SUPERBLOCK_BLOCKS: usize = 8;
+const SUPERBLOCK_SIZE: Index = BLOCK_SIZE * SUPERBLOCK_BLOCKS as Index;
+const SELECT_SAMPLE: Rank = 1024;
+
+/// A `BitString` with superblock/block rank counts and sampled select hints.
+#[derive(Debug, Clone)]
+pub struct IndexedBitString<S: Storage = Owned> {
+    bits: BitString<u64, S>,
+    superblocks: S::Slice<Rank>,
+    blocks: S::Slice<u16>,
+    one_hints: S::Slice<u32>,
+    zero_hints: S::Slice<u32>,
+}
+impl<S: Storage> IndexedBitString<S> {
+    pub fn get(&self, index: Index) -> Option<Bit> {
+        self.bits.get(index)
+    }
+    pub fn len(&self) -> Index {
+        self.bits.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+    pub fn count_ones(&self) -> Rank {
+        *self.superblocks.last().unwrap()
+    }
+    pub fn count_zeros(&self) -> Rank {
+        self.len() - self.count_ones()
+    }
+    pub fn as_bit_string(&self) -> &BitString<u64, S> {
+        &self.bits
+    }
+    pub fn into_bit_string(self) -> BitString<u64, S> {
+        self.bits
+    }
+
+    fn zeros_before_superblock(&self, superblock: usize) -> Rank {
+        (superblock as Index * SUPERBLOCK_SIZE).min(self.len()) - self.superblocks[superblock]
+    }
+    fn zeros_before_block(&self, block: usize) -> Rank {
+        (block % SUPERBLOCK_BLOCKS) as Rank * BLOCK_SIZE - self.blocks[block] as Rank
+    }
+    fn hinted_superblocks(hints: &[u32], rank: Rank, superblocks: usize) -> (usize, usize) {
+        let hint = ((rank - 1) / SELECT_SAMPLE) as usize;
+        let start = hints[hint] as usize;
+        let end = hints.get(hint + 1).map_or(superblocks, |&h| h as usize + 1);
+        (start, end)
+    }
+    fn superblock_blocks(&self, superblock: usize) -> (usize, usize) {
+        let start = superblock * SUPERBLOCK_BLOCKS;
+        (start, (start + SUPERBLOCK_BLOCKS).min(self.blocks.len()))
+    }
+}
+impl IndexedBitString {
+    pub fn new() -> Self {
+        IndexedBitString {
+            bits: BitString::new(),
+            superblocks: vec![0],
+            blocks: Vec::new(),
+            one_hints: Vec::new(),
+            zero_hints: Vec::new(),
+        }
+    }
+
+    /// Appends a bit, keeping the directory up to date.
+    pub fn push(&mut self, bit: Bit) {
+        let index = self.len();
+        let ones = self.count_ones();
+        if index.is_multiple_of(SUPERBLOCK_SIZE) {
+            // The total count so far becomes the count before the new superblock.
+            self.superblocks.push(ones);
+        }
+        let superblock = self.superblocks.len() - 2;
+        if index.is_multiple_of(BLOCK_SIZE) {
+            self.blocks
+                .push((ones - self.superblocks[superblock]) as u16);
+        }
+        self.bits.push(bit);
+        *self.superblocks.last_mut().unwrap() += bit as Rank;
+
+        let ones = self.count_ones();
+        let zeros = self.count_zeros();
+        while self.one_hints.len() as Rank * SELECT_SAMPLE < ones {
+            self.one_hints.push(superblock as u32);
+        }
+        while self.zero_hints.len() as Rank * SELECT_SAMPLE < zeros {
+            self.zero_hints.push(superblock as u32);
+        }
+    }
+    pub fn shrink_to_fit(&mut self) {
+        self.bits.shrink_to_fit();
+        self.superblocks.shrink_to_fit();
+        self.blocks.shrink_to_fit();
+        self.one_hints.shrink_to_fit();
+        self.zero_hints.shrink_to_fit();
+    }
+}
+impl Default for IndexedBitString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl From<BitString> for IndexedBitString {
+    fn from(bits: BitString) -> Self {
+        let fixnums = bits.as_fixnums();
+        let mut superblocks = Vec::with_capacity(fixnums.len() / SUPERBLOCK_BLOCKS + 2);
+        let mut blocks = Vec::with_capacity(fixnums.len());
+        let mut one_hints = Vec::new();
+        let mut zero_hints = Vec::new();
+
+        let mut ones = 0;
+        for (i, chunk) in fixnums.chunks(SUPERBLOCK_BLOCKS).enumerate() {
+            superblocks.push(ones);
+            let mut relative = 0;
+            for b in chunk {
+                blocks.push(relative as u16);
+                relative += b.pop_count() as Rank;
+            }
+            ones += relative;
+
+            let zeros = ((i + 1) as Index * SUPERBLOCK_SIZE).min(bits.len()) - ones;
+            while one_hints.len() as Rank * SELECT_SAMPLE < ones {
+                one_hints.push(i as u32);
+            }
+            while zero_hints.len() as Rank * SELECT_SAMPLE < zeros {
+                zero_hints.push(i as u32);
+            }
+        }
+        superblocks.push(ones);
+
+        IndexedBitString {
+            bits,
+            superblocks,
+            blocks,
+            one_hints,
+            zero_hints,
+        }
+    }
+}
+impl<S: Storage> RankBit for IndexedBitString<S> {
+    fn rank_one(&self, index: Index) -> Rank {
+        if index >= self.len() {
+            return self.count_ones();
+        }
+        let block = (index / BLOCK_SIZE) as usize;
+        let superblock = block / SUPERBLOCK_BLOCKS;
+        self.superblocks[superblock]
+            + self.blocks[block] as Rank
+            + self.bits.as_fixnums()[block].rank_one(index % BLOCK_SIZE)
+    }
+}
+impl<S: Storage> SelectZero for IndexedBitString<S> {
+    fn select_zero(&self, rank: Rank) -> Option<Index> {
+        if rank == 0 || rank > self.count_zeros() {
+            return None;
+        }
+
+        let (start, end) = Self::hinted_superblocks(&self.zero_hints, rank, self.superblocks.len());
+        let superblock = find_last(start, end, |i| self.zeros_before_superblock(i) < rank);
+        let rest = rank - self.zeros_before_superblock(superblock);
+
+        let (start, end) = self.superblock_blocks(superblock);
+        let block = find_last(start, end, |i| self.zeros_before_block(i) < rest);
+        let rest = rest - self.zeros_before_block(block);
+        let offset = self.bits.as_fixnums()[block].select_zero(rest).unwrap();
+        Some(block as Index * BLOCK_SIZE + offset)
+    }
+}
+impl<S: Storage> SelectOne for IndexedBitString<S> {
+    fn select_one(&self, rank: Rank) -> Option<Index> {
+        if rank == 0 || rank > self.count_ones() {
+            return None;
+        }
+
+        let (start, end) = Self::hinted_superblocks(&self.one_hints, rank, self.superblocks.len());
+        let superblock = find_last(start, end, |i| self.superblocks[i] < rank);
+        let rest = rank - self.superblocks[superblock];
+
+        let (start, end) = self.superblock_blocks(superblock);
+        let block = find_last(start, end, |i| (self.blocks[i] as Rank) < rest);
+        let rest = rest - self.blocks[block] as Rank;
+        let offset = self.bits.as_fixnums()[block].select_one(rest).unwrap();
+        Some(block as Index * BLOCK_SIZE + offset)
+    }
+}
+impl<S: Storage> PredZero for IndexedBitString<S> {
+    fn pred_zero(&self, index: Index) -> Option<Index> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = index.min(self.len() - 1);
+        let block = index / BLOCK_SIZE;
+        if let Some(i) = self.bits.as_fixnums()[block as usize].pred_zero(index % BLOCK_SIZE) {
+            Some(block * BLOCK_SIZE + i)
+        } else {
+            ops::naive_pred_zero(self, index)
+        }
+    }
+}
+impl<S: Storage> PredOne for IndexedBitString<S> {
+    fn pred_one(&self, index: Index) -> Option<Index> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = index.min(self.len() - 1);
+        let block = index / BLOCK_SIZE;
+        if let Some(i) = self.bits.as_fixnums()[block as usize].pred_one(index % BLOCK_SIZE) {
+            Some(block * BLOCK_SIZE + i)
+        } else {
+            ops::naive_pred_one(self, index)
+        }
+    }
+}
+impl<S: Storage> SuccZero for IndexedBitString<S> {
+    fn succ_zero(&self, index: Index) -> Option<Index> {
+        if index >= self.len() {
+            return None;
+        }
+        let block = index / BLOCK_SIZE;
+        match self.bits.as_fixnums()[block as usize].succ_zero(index % BLOCK_SIZE) {
+            Some(i) if block * BLOCK_SIZE + i < self.len() => Some(block * BLOCK_SIZE + i),
+            Some(_) => None,
+            None => self.select_zero(self.rank_zero(index) + 1),
+        }
+    }
+}
+impl<S: Storage> SuccOne for IndexedBitString<S> {
+    fn succ_one(&self, index: Index) -> Option<Index> {
+        if index >= self.len() {
+            return None;
+        }
+        let block = index / BLOCK_SIZE;
+        if let Some(i) = self.bits.as_fixnums()[block as usize].succ_one(index % BLOCK_SIZE) {
+            Some(block * BLOCK_SIZE + i)
+        } else {
+            self.select_one(self.rank_one(index) + 1)
+        }
+    }
+}
+impl<S: Storage> ops::ExternalByteSize for IndexedBitString<S> {
+    fn external_byte_size(&self) -> u64 {
+        self.bits.external_byte_size()
+            + self.superblocks.len() as u64 * mem::size_of::<Rank>() as u64
+            + self.blocks.len() as u64 * mem::size_of::<u16>() as u64
+            + (self.one_hints.len() + self.zero_hints.len()) as u64 * mem::size_of::<u32>() as u64
+    }
+}
+
+impl<S: Storage> WriteTo for IndexedBitString<S> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, MAGIC)?;
+        self.bits.write_to(writer)?;
+        serialize::write_slice(writer, &self.superblocks)?;
+        serialize::write_slice(writer, &self.blocks)?;
+        serialize::write_slice(writer, &self.one_hints)?;
+        serialize::write_slice(writer, &self.zero_hints)
+    }
+}
+impl ReadFrom for IndexedBitString {
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, MAGIC)?;
+        IndexedBitString {
+            bits: BitString::read_from(&mut reader)?,
+            superblocks: serialize::read_vec(&mut reader)?,
+            blocks: serialize::read_vec(&mut reader)?,
+            one_hints: serialize::read_vec(&mut reader)?,
+            zero_hints: serialize::read_vec(&mut reader)?,
+        }
+        .validate()
+    }
+}
+impl<'a> FromBytes<'a> for IndexedBitString<Borrowed<'a>> {
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, MAGIC)?;
+        IndexedBitString {
+            bits: BitString::from_bytes(bytes)?,
+            superblocks: serialize::view_slice(bytes)?,
+            blocks: serialize::view_slice(bytes)?,
+            one_hints: serialize::view_slice(bytes)?,
+            zero_hints: serialize::view_slice(bytes)?,
+        }
+        .validate()
+    }
+}
+impl<S: Storage> IndexedBitString<S> {
+    fn validate(self) -> io::Result<Self> {
+        let fixnums = self.bits.as_fixnums().len();
+        if self.blocks.len() != fixnums
+            || self.superblocks.len() != fixnums.div_ceil(SUPERBLOCK_BLOCKS) + 1
+        {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected rank directory size: fixnums={fixnums}, superblocks={}, blocks={}",
+                self.superblocks.len(),
+                self.blocks.len()
+            )));
+        }
+        Ok(self)
+    }
+}
+
+// Returns the last index in `start..end` satisfying `f` (`f` must hold for `start` and be monotone).
+pub(super) fn find_last<F>(mut start: usize, mut end: usize, f: F) -> usize
+where
+    F: Fn(usize) -> bool,
+{
+    debug_assert!(f(start));
+    while end - start > 1 {
+        let mid = start + (end - start) / 2;
+        if f(mid) {
+            start = mid;
+        } else {
+            end = mid;
+        }
+    }
+    start
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::ops::*;
+    use super::super::{Bit, BitString, Index, Rank};
+    use super::*;
+    use crate::serialize::{FromBytes, ReadFrom, WriteTo};
+    use crate::storage::Borrowed;
+
+    fn check(bits: &[Bit]) {
+        let ones = (0..bits.len() as Index)
+            .filter(|&i| bits[i as usize])
+            .collect::<Vec<_>>();
+        let zeros = (0..bits.len() as Index)
+            .filter(|&i| !bits[i as usize])
+            .collect::<Vec<_>>();
+        let ibs = IndexedBitString::from(bits.iter().cloned().collect::<BitString>());
+        assert_eq!(ibs.len(), bits.len() as Index);
+        assert_eq!(ibs.count_ones(), ones.len() as Rank);
+        for i in 0..bits.len() as Index {
+            let rank_one = ones.partition_point(|&j| j <= i);
+            let rank_zero = zeros.partition_point(|&j| j <= i);
+
+            // rank
+            assert_eq!(ibs.rank_one(i), rank_one as Rank);
+            assert_eq!(ibs.rank_zero(i), rank_zero as Rank);
+
+            // select
+            assert_eq!(ibs.select_one(i + 1), ones.get(i as usize).cloned());
+            assert_eq!(ibs.select_zero(i + 1), zeros.get(i as usize).cloned());
+
+            // pred
+            let pred_one = rank_one.checked_sub(1).map(|r| ones[r]);
+            let pred_zero = rank_zero.checked_sub(1).map(|r| zeros[r]);
+            assert_eq!(ibs.pred_one(i), pred_one);
+            assert_eq!(ibs.pred_zero(i), pred_zero);
+
+            // succ
+            let succ_one = ones.get(ones.partition_point(|&j| j < i)).cloned();
+            let succ_zero = zeros.get(zeros.partition_point(|&j| j < i)).cloned();
+            assert_eq!(ibs.succ_one(i), succ_one);
+            assert_eq!(ibs.succ_zero(i), succ_zero);
+        }
+        assert_eq!(ibs.select_zero(0), None);
+        assert_eq!(ibs.select_one(0), None);
+    }
+
+    #[test]
+    fn same_as_linear_fid() {
+        let bits = (0..700)
+            .map(|i| i % 5 == 0 || i % 7 == 0)
+            .collect::<Vec<_>>();
+        let expected = LinearFid::new(bits.iter().cloned());
+        let ibs = bits.iter().cloned().collect::<BitString>().into();
+        fn assert_fid<T: Fid>(_: &T) {}
+        assert_fid::<IndexedBitString>(&ibs);
+        for i in 0..bits.len() as Index {
+            assert_eq!(ibs.rank_one(i), expected.rank_one(i));
+            assert_eq!(ibs.select_zero(i + 1), expected.select_zero(i + 1));
+            assert_eq!(ibs.pred_zero(i), expected.pred_zero(i));
+            assert_eq!(ibs.succ_one(i), expected.succ_one(i));
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        check(&[]);
+        check(&(0..1000).map(|i| i % 5 == 0).collect::<Vec<_>>());
+        check(&(0..3000).map(|i| i % 7 != 0).collect::<Vec<_>>());
+        check(&(0..4096).map(|i| i % 3 == 0).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn serialize() {
+        let bits = (0..5000).map(|i| i % 11 < 3).collect::<BitString>();
+        let ibs = IndexedBitString::from(bits);
+        let mut buf = Vec::new();
+        ibs.write_to(&mut buf).unwrap();
+
+        let read = IndexedBitString::read_from(&buf[..]).unwrap();
+        let aligned = serialize::aligned(&buf);
+        let view =
+            IndexedBitString::<Borrowed>::from_bytes(&mut serialize::aligned_bytes(&aligned))
+                .unwrap();
+        assert_eq!(read.len(), ibs.len());
+        assert_eq!(view.len(), ibs.len());
+        for i in 0..5000 {
+            assert_eq!(read.rank_one(i), ibs.rank_one(i));
+            assert_eq!(read.select_zero(i + 1), ibs.select_zero(i + 1));
+            assert_eq!(view.select_one(i + 1), ibs.select_one(i + 1));
+        }
+    }
+
+    #[test]
+    fn push() {
+        for len in [0, 1, 64, 65, 511, 512, 513, 3000] {
+            let bits = (0..len).map(|i| i % 3 == 0 || i > 2000);
+            let mut ibs = IndexedBitString::new();
+            for bit in bits.clone() {
+                ibs.push(bit);
+            }
+            let expected = IndexedBitString::from(bits.collect::<BitString>());
+            assert_eq!(format!("{ibs:?}"), format!("{expected:?}"));
+        }
+    }
+
+    #[test]
+    fn long_runs() {
+        let mut x = 0x2545_F491_4F6C_DD1Du64;
+        let bits = (0..20_000)
+            .map(|i| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                if (5_000..12_000).contains(&i) {
+                    false
+                } else if (15_000..18_000).contains(&i) {
+                    true
+                } else {
+                    x & 1 == 1
+                }
+            })
+            .collect::<Vec<_>>();
+        check(&bits);
+    }
+}
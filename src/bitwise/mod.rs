@@ -1,10 +1,18 @@
 #[doc(inline)]
 pub use self::string::BitString;
 
+#[doc(inline)]
+pub use self::indexed_bit_string::IndexedBitString;
+
+#[doc(inline)]
+pub use self::elias_fano_nnd::EliasFanoNnd;
+
 #[doc(inline)]
 pub use self::sparse_one_nnd::SparseOneNnd;
 
+pub mod elias_fano_nnd;
 pub mod fixnum;
+pub mod indexed_bit_string;
 pub mod ops;
 pub mod sparse_one_nnd;
 pub mod string;
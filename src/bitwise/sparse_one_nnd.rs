@@ -1,27 +1,203 @@
-use std;
+use std::io::{self, Read, Write};
 use std::iter;
+use std::mem;
 
+use super::indexed_bit_string::find_last;
 use super::ops;
-use super::ops::{PredOne, RankBit, SelectOne, SuccOne};
+use super::ops::{PredOne, PredZero, RankBit, SelectOne, SelectZero, SuccOne, SuccZero};
 use super::{Bit, BitString, Index, Rank};
+use crate::serialize::{self, FromBytes, Pod, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
 
-// TODO: parameter
-const SMALL_SIZE: usize = (u8::MAX as usize) + 1;
-const MIDDLE_SIZE: usize = SMALL_SIZE * 8;
-const MIDDLE_COUNT: usize = 32;
-const LARGE_SIZE: usize = MIDDLE_SIZE * MIDDLE_COUNT;
+const MAGIC: &[u8; 4] = b"SONN";
 
+/// A nearest neighbour dictionary for sparse one bits.
+///
+/// The bits are divided into small blocks of `SMALL_SIZE` bits (at most 256), each of which is
+/// encoded as the number of its one bits followed by their offsets in bytes.
+/// `MIDDLE_SMALLS` small blocks form a middle block and `LARGE_MIDDLES` middle blocks form a large
+/// block, and the positions and ranks of the blocks are recorded to skip to them.
+/// The positions of the middle blocks within a large block must fit in `u16`, which is checked
+/// at compile time.
+/// Larger blocks reduce the space (see `ExternalByteSize`) at the expense of longer scans.
 #[derive(Debug, Clone)]
-pub struct SparseOneNnd {
-    smalles: Vec<u8>,
-    middles: Vec<Base<u16>>,
-    larges: Vec<Base<u64>>,
+pub struct SparseOneNnd<
+    S: Storage = Owned,
+    const SMALL_SIZE: usize = 256,
+    const MIDDLE_SMALLS: usize = 8,
+    const LARGE_MIDDLES: usize = 32,
+> {
+    len: Index,
+    smalles: S::Slice<u8>,
+    middles: S::Slice<Base<u16>>,
+    larges: S::Slice<Base<u64>>,
 }
-impl SparseOneNnd {
-    fn from_one_indices<I>(iter: I) -> Self
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize>
+    SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
+    const MIDDLE_SIZE: usize = SMALL_SIZE * MIDDLE_SMALLS;
+    const LARGE_SIZE: usize = Self::MIDDLE_SIZE * LARGE_MIDDLES;
+
+    // The largest offset of a middle block in `smalles` relative to its large block,
+    // where every small block before it is full (a count byte followed by `SMALL_SIZE` offsets).
+    const MAX_MIDDLE_OFFSET: usize =
+        (Self::LARGE_SIZE - Self::MIDDLE_SIZE) / SMALL_SIZE * (SMALL_SIZE + 1);
+
+    pub fn len(&self) -> Index {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn count_ones(&self) -> Rank {
+        self.len.checked_sub(1).map_or(0, |i| self.rank_one(i))
+    }
+    pub fn count_zeros(&self) -> Rank {
+        self.len - self.count_ones()
+    }
+
+    // Returns the small block containing `index`.
+    //
+    // If `index` is after the last small block, the returned `index` is `smalles.len()`.
+    fn find_small(&self, index: Index) -> Small {
+        if self.larges.is_empty() {
+            return Small {
+                index: 0,
+                offset: 0,
+                rank: 0,
+                last_one: None,
+            };
+        }
+        let large_index = ((index / Self::LARGE_SIZE as Index) as usize).min(self.larges.len() - 1);
+        let large_base = &self.larges[large_index];
+        let middle_index =
+            ((index / Self::MIDDLE_SIZE as Index) as usize).min(self.middles.len() - 1);
+        let middle_base = &self.middles[middle_index];
+
+        let mut small = Small {
+            index: large_base.small_index as usize + middle_base.small_index as usize,
+            offset: middle_index as Index * Self::MIDDLE_SIZE as Index,
+            rank: large_base.rank as Rank + middle_base.rank as Rank,
+            last_one: None,
+        };
+        while small.offset + SMALL_SIZE as Index <= index {
+            let deltas = self.small_deltas(small.index);
+            if let Some(d) = deltas.last() {
+                small.last_one = Some(small.offset + *d as Index);
+            }
+            small.rank += deltas.len() as Rank;
+            small.index += deltas.len() + 1;
+            small.offset += SMALL_SIZE as Index;
+            if small.index == self.smalles.len() {
+                // There are no more blocks after the last one bit.
+                break;
+            }
+        }
+        small
+    }
+
+    // Returns the offsets of the one bits in the small block starting at `smalles[small_index]`.
+    fn small_deltas(&self, small_index: usize) -> &[u8] {
+        let count = self.smalles[small_index] as usize;
+        &self.smalles[small_index + 1..][..count]
+    }
+
+    fn check_block_sizes<R: Read>(reader: &mut R) -> io::Result<()> {
+        let expected = [SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES];
+        let mut actual = [0; 3];
+        for size in &mut actual {
+            *size = serialize::read_u64(reader)? as usize;
+        }
+        if actual != expected {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected block sizes: expected={expected:?}, actual={actual:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate(self) -> io::Result<Self> {
+        if !self.is_consistent() {
+            return Err(serialize::invalid_data(format!(
+                "Inconsistent sparse one blocks: len={}, smalles={}, middles={}, larges={}",
+                self.len,
+                self.smalles.len(),
+                self.middles.len(),
+                self.larges.len()
+            )));
+        }
+        Ok(self)
+    }
+
+    // Walks the small blocks and checks that they are laid out as `from_one_indices` builds them.
+    fn is_consistent(&self) -> bool {
+        let mut small_index = 0;
+        let mut rank = 0;
+        let mut offset = 0;
+        let mut large = Base::new(0, 0);
+        let mut last_one = None;
+        while small_index < self.smalles.len() {
+            if offset % Self::LARGE_SIZE == 0 {
+                large = Base::new(small_index, rank);
+                match self.larges.get(offset / Self::LARGE_SIZE) {
+                    Some(b) if b.small_index == small_index as u64 && b.rank == rank as u64 => {}
+                    _ => return false,
+                }
+            }
+            if offset % Self::MIDDLE_SIZE == 0 {
+                match self.middles.get(offset / Self::MIDDLE_SIZE) {
+                    Some(b)
+                        if b.small_index as usize == small_index - large.small_index
+                            && b.rank as usize == rank - large.rank => {}
+                    _ => return false,
+                }
+            }
+
+            let count = self.smalles[small_index] as usize;
+            let Some(deltas) = self.smalles[small_index + 1..].get(..count) else {
+                return false;
+            };
+            if deltas.windows(2).any(|w| w[0] >= w[1])
+                || deltas.last().is_some_and(|d| *d as usize >= SMALL_SIZE)
+            {
+                return false;
+            }
+            if let Some(d) = deltas.last() {
+                last_one = Some(offset + *d as usize);
+            }
+            rank += count;
+            small_index += count + 1;
+            offset += SMALL_SIZE;
+        }
+
+        // The blocks end at the one containing the last one bit.
+        let end = last_one.map_or(0, |i| (i / SMALL_SIZE + 1) * SMALL_SIZE);
+        offset == end
+            && last_one.is_none_or(|i| (i as Index) < self.len)
+            && self.middles.len() == end.div_ceil(Self::MIDDLE_SIZE)
+            && self.larges.len() == end.div_ceil(Self::LARGE_SIZE)
+    }
+}
+impl<const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize>
+    SparseOneNnd<Owned, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+{
+    fn from_one_indices<I>(len: Index, iter: I) -> Self
     where
         I: Iterator<Item = Index>,
     {
+        const {
+            assert!(
+                0 < SMALL_SIZE
+                    && SMALL_SIZE <= 0x100
+                    && 0 < MIDDLE_SMALLS
+                    && 0 < LARGE_MIDDLES
+                    && Self::MAX_MIDDLE_OFFSET <= u16::MAX as usize,
+                "Invalid block sizes"
+            )
+        };
+
         let mut larges = Vec::new();
         let mut middles = Vec::new();
         let mut smalles = Vec::new();
@@ -33,20 +209,20 @@ impl SparseOneNnd {
         let mut next_small_i = 0;
         for (rank, one_index) in iter.enumerate() {
             let one_index = one_index as usize;
-            let small_base = smalles.len();
             while next_small_i <= one_index {
-                small_count_index = smalles.len();
+                let small_base = smalles.len();
+                small_count_index = small_base;
                 smalles.push(0);
                 prev_index = next_small_i;
 
-                if next_small_i % LARGE_SIZE == 0 {
+                if next_small_i % Self::LARGE_SIZE == 0 {
                     large_prev = Base::new(small_base, rank);
                     larges.push(Base::new(
                         large_prev.small_index as u64,
                         large_prev.rank as u64,
                     ));
                 }
-                if next_small_i % MIDDLE_SIZE == 0 {
+                if next_small_i % Self::MIDDLE_SIZE == 0 {
                     middles.push(Base::new(
                         (small_base - large_prev.small_index) as u16,
                         (rank - large_prev.rank) as u16,
@@ -65,60 +241,54 @@ impl SparseOneNnd {
         smalles.shrink_to_fit();
 
         SparseOneNnd {
+            len,
             larges,
             middles,
             smalles,
         }
     }
 }
-impl iter::FromIterator<Bit> for SparseOneNnd {
+impl<const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize>
+    iter::FromIterator<Bit> for SparseOneNnd<Owned, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+{
     fn from_iter<I>(bits: I) -> Self
     where
         I: IntoIterator<Item = Bit>,
     {
-        Self::from_one_indices(
-            bits.into_iter()
-                .enumerate()
-                .filter(|e| e.1)
-                .map(|e| e.0 as Index),
-        )
+        Self::from(bits.into_iter().collect::<BitString>())
     }
 }
-impl From<BitString> for SparseOneNnd {
+impl<const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize>
+    From<BitString> for SparseOneNnd<Owned, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+{
     fn from(bits: BitString) -> Self {
-        Self::from_one_indices(bits.one_indices())
+        Self::from_one_indices(bits.len(), bits.one_indices())
     }
 }
-impl RankBit for SparseOneNnd {
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> RankBit
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
     fn rank_one(&self, index: Index) -> Rank {
-        let large_index = ((index / LARGE_SIZE as Index) as usize).min(self.larges.len() - 1);
-        let large_base = &self.larges[large_index];
-        let middle_index = ((index / MIDDLE_SIZE as Index) as usize).min(self.middles.len() - 1);
-        let middle_base = &self.middles[middle_index];
-        let middle_offset = middle_index as Index * MIDDLE_SIZE as Index;
-
-        let mut small_index = large_base.small_index as usize + middle_base.small_index as usize;
-        let mut curr_rank = large_base.rank as Rank + middle_base.rank as Rank;
-        let mut curr_index = /*large_offset + */ middle_offset;
-        while curr_index + SMALL_SIZE as Index <= index {
-            curr_rank += self.smalles[small_index] as Rank;
-            small_index += self.smalles[small_index] as usize + 1;
-            curr_index += SMALL_SIZE as Index;
+        let small = self.find_small(index);
+        if small.index == self.smalles.len() {
+            return small.rank;
         }
-
-        let count = self.smalles[small_index] as usize;
-        assert!(index >= curr_index, "{index}, {curr_index}");
-        let delta = (index - curr_index) as u8;
-
-        curr_rank
-            + self.smalles[small_index + 1..]
+        let delta = index - small.offset;
+        small.rank
+            + self
+                .small_deltas(small.index)
                 .iter()
-                .take(count)
-                .take_while(|i| **i <= delta)
+                .take_while(|d| **d as Index <= delta)
                 .count() as Rank
     }
 }
-impl SelectOne for SparseOneNnd {
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> SelectOne
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
     fn select_one(&self, rank: Rank) -> Option<Index> {
         if rank == 0 {
             return None;
@@ -129,19 +299,19 @@ impl SelectOne for SparseOneNnd {
             .larges
             .binary_search_by_key(&rank, |e| e.rank as Rank)
             .unwrap_or_else(|i| i.saturating_sub(1));
-        let large_base = &self.larges[i];
-        let large_index = i as Index * LARGE_SIZE as Index;
+        let large_base = self.larges.get(i)?;
+        let large_index = i as Index * Self::LARGE_SIZE as Index;
         let middle_rank = rank - large_base.rank as Rank;
 
-        let middle_start = i * MIDDLE_COUNT;
-        let middle_end = (middle_start + MIDDLE_COUNT).min(self.middles.len());
+        let middle_start = i * LARGE_MIDDLES;
+        let middle_end = (middle_start + LARGE_MIDDLES).min(self.middles.len());
         let middles = &self.middles[middle_start..middle_end];
         {
             let i = middles
                 .binary_search_by_key(&middle_rank, |e| e.rank as Rank)
                 .unwrap_or_else(|i| i - 1);
             let middle_base = &middles[i];
-            let middle_index = i as Index * MIDDLE_SIZE as Index;
+            let middle_index = i as Index * Self::MIDDLE_SIZE as Index;
 
             let mut small_index =
                 large_base.small_index as usize + middle_base.small_index as usize;
@@ -162,29 +332,246 @@ impl SelectOne for SparseOneNnd {
         }
     }
 }
-impl PredOne for SparseOneNnd {
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> PredOne
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
     fn pred_one(&self, index: Index) -> Option<Index> {
-        ops::naive_pred_one(self, index)
+        let small = self.find_small(index);
+        if small.index < self.smalles.len() {
+            let delta = index - small.offset;
+            let deltas = self.small_deltas(small.index);
+            if let Some(d) = deltas.iter().rev().find(|d| **d as Index <= delta) {
+                return Some(small.offset + *d as Index);
+            }
+        }
+        match small.last_one {
+            Some(one) => Some(one),
+            None => self.select_one(small.rank),
+        }
     }
 }
-impl SuccOne for SparseOneNnd {
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> SuccOne
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
     fn succ_one(&self, index: Index) -> Option<Index> {
-        ops::naive_succ_one(self, index)
+        let small = self.find_small(index);
+        let (mut small_index, mut offset) = (small.index, small.offset);
+        if small_index < self.smalles.len() {
+            let delta = index - offset;
+            let deltas = self.small_deltas(small_index);
+            if let Some(d) = deltas.iter().find(|d| **d as Index >= delta) {
+                return Some(offset + *d as Index);
+            }
+            small_index += deltas.len() + 1;
+            offset += SMALL_SIZE as Index;
+        }
+        while small_index < self.smalles.len() {
+            if let Some(d) = self.small_deltas(small_index).first() {
+                return Some(offset + *d as Index);
+            }
+            small_index += 1;
+            offset += SMALL_SIZE as Index;
+        }
+        None
+    }
+}
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> SelectZero
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
+    fn select_zero(&self, rank: Rank) -> Option<Index> {
+        if rank == 0 || rank > self.count_zeros() {
+            return None;
+        }
+        let rank = rank - 1;
+        if self.larges.is_empty() {
+            return Some(rank);
+        }
+
+        let zeros_before = |offset: Index, ones: Rank| offset - ones;
+        let i = find_last(0, self.larges.len(), |i| {
+            zeros_before(i as Index * Self::LARGE_SIZE as Index, self.larges[i].rank) <= rank
+        });
+        let large_base = &self.larges[i];
+        let middle_start = i * LARGE_MIDDLES;
+        let middle_end = (middle_start + LARGE_MIDDLES).min(self.middles.len());
+        let j = find_last(middle_start, middle_end, |j| {
+            let ones = large_base.rank + self.middles[j].rank as Rank;
+            zeros_before(j as Index * Self::MIDDLE_SIZE as Index, ones) <= rank
+        });
+        let middle_base = &self.middles[j];
+
+        let mut small_index = large_base.small_index as usize + middle_base.small_index as usize;
+        let mut offset = j as Index * Self::MIDDLE_SIZE as Index;
+        let mut rest = rank - zeros_before(offset, large_base.rank + middle_base.rank as Rank);
+        while small_index < self.smalles.len() {
+            let deltas = self.small_deltas(small_index);
+            let zeros = (SMALL_SIZE - deltas.len()) as Rank;
+            if rest < zeros {
+                // The `rest`-th zero is shifted by every one bit at or before it.
+                for &d in deltas {
+                    if d as Index <= rest {
+                        rest += 1;
+                    } else {
+                        break;
+                    }
+                }
+                break;
+            }
+            rest -= zeros;
+            small_index += deltas.len() + 1;
+            offset += SMALL_SIZE as Index;
+        }
+        Some(offset + rest)
+    }
+}
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> PredZero
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
+    fn pred_zero(&self, index: Index) -> Option<Index> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = index.min(self.len - 1);
+        let small = self.find_small(index);
+        if small.index == self.smalles.len() {
+            return Some(index);
+        }
+        let mut delta = index - small.offset;
+        for &d in self.small_deltas(small.index).iter().rev() {
+            if (d as Index) < delta {
+                break;
+            } else if d as Index == delta {
+                if delta == 0 {
+                    return ops::naive_pred_zero(self, index);
+                }
+                delta -= 1;
+            }
+        }
+        Some(small.offset + delta)
+    }
+}
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> SuccZero
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
+    fn succ_zero(&self, index: Index) -> Option<Index> {
+        if index >= self.len {
+            return None;
+        }
+        let small = self.find_small(index);
+        if small.index == self.smalles.len() {
+            return Some(index);
+        }
+        let mut delta = index - small.offset;
+        for &d in self.small_deltas(small.index) {
+            if (d as Index) > delta {
+                break;
+            } else if d as Index == delta {
+                delta += 1;
+            }
+        }
+        if delta == SMALL_SIZE as Index {
+            return self.select_zero(self.rank_zero(index) + 1);
+        }
+        Some(small.offset + delta).filter(|&i| i < self.len)
     }
 }
-impl ops::ExternalByteSize for SparseOneNnd {
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize>
+    ops::ExternalByteSize for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
     fn external_byte_size(&self) -> u64 {
-        self.smalles.len() as u64
-            + self.middles.len() as u64 * std::mem::size_of::<Base<u16>>() as u64
-            + self.larges.len() as u64 * std::mem::size_of::<Base<u64>>() as u64
+        mem::size_of_val(&self.len) as u64
+            + self.smalles.len() as u64
+            + self.middles.len() as u64 * mem::size_of::<Base<u16>>() as u64
+            + self.larges.len() as u64 * mem::size_of::<Base<u64>>() as u64
+    }
+}
+
+impl<S, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> WriteTo
+    for SparseOneNnd<S, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+where
+    S: Storage,
+{
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, MAGIC)?;
+        for size in [SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES] {
+            serialize::write_u64(writer, size as u64)?;
+        }
+        serialize::write_u64(writer, self.len)?;
+        serialize::write_slice(writer, &self.smalles)?;
+        serialize::write_slice(writer, &self.middles)?;
+        serialize::write_slice(writer, &self.larges)
+    }
+}
+impl<const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize> ReadFrom
+    for SparseOneNnd<Owned, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, MAGIC)?;
+        Self::check_block_sizes(&mut reader)?;
+        let len = serialize::read_u64(&mut reader)?;
+        let smalles = serialize::read_vec(&mut reader)?;
+        let middles = serialize::read_vec(&mut reader)?;
+        let larges = serialize::read_vec(&mut reader)?;
+        SparseOneNnd {
+            len,
+            smalles,
+            middles,
+            larges,
+        }
+        .validate()
+    }
+}
+impl<'a, const SMALL_SIZE: usize, const MIDDLE_SMALLS: usize, const LARGE_MIDDLES: usize>
+    FromBytes<'a> for SparseOneNnd<Borrowed<'a>, SMALL_SIZE, MIDDLE_SMALLS, LARGE_MIDDLES>
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, MAGIC)?;
+        Self::check_block_sizes(bytes)?;
+        let len = serialize::read_u64(bytes)?;
+        let smalles = serialize::view_slice(bytes)?;
+        let middles = serialize::view_slice(bytes)?;
+        let larges = serialize::view_slice(bytes)?;
+        SparseOneNnd {
+            len,
+            smalles,
+            middles,
+            larges,
+        }
+        .validate()
     }
 }
 
+#[derive(Debug)]
+struct Small {
+    index: usize,
+    offset: Index,
+    rank: Rank,
+    last_one: Option<Index>,
+}
+
 #[derive(Debug, Clone, Copy)]
+#[repr(C)]
 struct Base<T: Copy> {
     small_index: T,
     rank: T,
 }
+unsafe impl<T: Pod> Pod for Base<T> {
+    fn to_le(self) -> Self {
+        Base::new(self.small_index.to_le(), self.rank.to_le())
+    }
+}
 
 impl<T: Copy> Base<T> {
     #[inline]
@@ -198,6 +585,8 @@ mod test {
     use super::super::ops::*;
     use super::super::{Bit, Index, Rank};
     use super::*;
+    use crate::serialize::{FromBytes, ReadFrom, WriteTo};
+    use crate::storage::Borrowed;
 
     #[test]
     fn it_works() {
@@ -223,4 +612,203 @@ mod test {
             assert_eq!(nnd.succ_one(i as Index), expected.succ_one(i as Index));
         }
     }
+
+    #[test]
+    fn serialize() {
+        let bits = (0..10000).map(|i| Bit::from(i % 7 == 0));
+        let nnd = bits.collect::<SparseOneNnd>();
+        let mut buf = Vec::new();
+        nnd.write_to(&mut buf).unwrap();
+
+        let read = <SparseOneNnd>::read_from(&buf[..]).unwrap();
+        let aligned = serialize::aligned(&buf);
+        let view =
+            SparseOneNnd::<Borrowed>::from_bytes(&mut serialize::aligned_bytes(&aligned)).unwrap();
+        for i in 0..10000 {
+            assert_eq!(read.rank_one(i), nnd.rank_one(i));
+            assert_eq!(read.select_one(i + 1), nnd.select_one(i + 1));
+            assert_eq!(view.rank_one(i), nnd.rank_one(i));
+            assert_eq!(view.select_one(i + 1), nnd.select_one(i + 1));
+        }
+    }
+
+    #[test]
+    fn skipped_blocks() {
+        // The empty blocks between the ones cross middle block boundaries.
+        let bits = (0..6000).map(|i| i == 0 || i == 5000).collect::<Vec<_>>();
+        let expected = LinearFid::new(bits.iter().cloned());
+        let nnd = bits.iter().cloned().collect::<SparseOneNnd>();
+        for i in (0..bits.len() as Index).step_by(7) {
+            assert_eq!(nnd.rank_one(i), expected.rank_one(i), "rank_one({i})");
+        }
+    }
+
+    #[test]
+    fn after_last_one() {
+        let nnd = (0..1000).map(|i| i == 3).collect::<SparseOneNnd>();
+        assert_eq!(nnd.rank_one(999), 1);
+    }
+
+    #[test]
+    fn dense_large_blocks() {
+        // The offsets of the middle blocks in a dense large block are close to `u16::MAX`.
+        type Nnd = SparseOneNnd<Owned, 64, 4, 253>;
+
+        let nnd = (0..70000).map(|_| true).collect::<Nnd>();
+        for i in (0..70000).step_by(7) {
+            assert_eq!(nnd.rank_one(i), i + 1, "rank_one({i})");
+            assert_eq!(nnd.select_one(i + 1), Some(i), "select_one({})", i + 1);
+        }
+        assert_eq!(nnd.rank_one(64796), 64797);
+        assert_eq!(nnd.count_ones(), 70000);
+    }
+
+    #[test]
+    fn block_sizes() {
+        type Small = SparseOneNnd<Owned, 64, 4, 16>;
+        type Large = SparseOneNnd<Owned, 256, 32, 8>;
+
+        let bits = (0..100000).map(|i| i % 97 == 0).collect::<Vec<_>>();
+        let expected = LinearFid::new(bits.iter().cloned());
+        let small = bits.iter().cloned().collect::<Small>();
+        let large = bits.iter().cloned().collect::<Large>();
+        for i in (0..bits.len() as Index).step_by(13) {
+            assert_eq!(small.rank_one(i), expected.rank_one(i));
+            assert_eq!(large.rank_one(i), expected.rank_one(i));
+            assert_eq!(small.select_one(i / 97 + 1), large.select_one(i / 97 + 1));
+        }
+        let default = bits.iter().cloned().collect::<SparseOneNnd>();
+        assert!(large.external_byte_size() < default.external_byte_size());
+        assert!(default.external_byte_size() < small.external_byte_size());
+
+        let mut buf = Vec::new();
+        small.write_to(&mut buf).unwrap();
+        assert!(Small::read_from(&buf[..]).is_ok());
+        let e = <SparseOneNnd>::read_from(&buf[..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn inconsistent_blocks() {
+        type Nnd = SparseOneNnd<Owned, 64, 2, 2>;
+        fn check(nnd: Nnd) {
+            let mut buf = Vec::new();
+            nnd.write_to(&mut buf).unwrap();
+            let e = Nnd::read_from(&buf[..]).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            let aligned = serialize::aligned(&buf);
+            let e = SparseOneNnd::<Borrowed, 64, 2, 2>::from_bytes(&mut serialize::aligned_bytes(
+                &aligned,
+            ))
+            .unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        }
+
+        let nnd = (0..1000).map(|i| i % 100 == 3).collect::<Nnd>();
+        let mut buf = Vec::new();
+        nnd.write_to(&mut buf).unwrap();
+        assert!(Nnd::read_from(&buf[..]).is_ok());
+        let empty = Nnd::from(BitString::new());
+        let mut buf = Vec::new();
+        empty.write_to(&mut buf).unwrap();
+        assert!(Nnd::read_from(&buf[..]).is_ok());
+
+        // The last one bit is out of `len`.
+        let mut bad = nnd.clone();
+        bad.len = 903;
+        check(bad);
+
+        // A count overruns the one offsets.
+        let mut bad = nnd.clone();
+        bad.smalles.truncate(bad.smalles.len() - 1);
+        check(bad);
+
+        // A one offset is out of its small block.
+        let mut bad = nnd.clone();
+        bad.smalles[1] = 64;
+        check(bad);
+
+        // An empty small block after the last one bit.
+        let mut bad = nnd.clone();
+        bad.smalles.push(0);
+        check(bad);
+
+        // A middle block is missing.
+        let mut bad = nnd.clone();
+        bad.middles.pop();
+        check(bad);
+
+        // A large block has a wrong rank.
+        let mut bad = nnd.clone();
+        bad.larges[1].rank += 1;
+        check(bad);
+
+        // Blocks without any one bit.
+        let mut bad = empty;
+        bad.smalles.push(0);
+        bad.middles.push(Base::new(0, 0));
+        bad.larges.push(Base::new(0, 0));
+        check(bad);
+    }
+
+    #[test]
+    fn pred_succ() {
+        fn check<const S: usize, const M: usize, const L: usize>(ones: &[Index], len: Index) {
+            let mut bits = BitString::new();
+            bits.resize(len);
+            for &i in ones {
+                bits.set(i, true);
+            }
+            let nnd = SparseOneNnd::<Owned, S, M, L>::from(bits);
+            for i in 0..len + 300 {
+                assert_eq!(nnd.pred_one(i), naive_pred_one(&nnd, i), "pred_one({i})");
+                assert_eq!(nnd.succ_one(i), naive_succ_one(&nnd, i), "succ_one({i})");
+            }
+        }
+
+        let ones = [3, 4, 700, 2047, 2048, 5000, 70000, 70255, 70256, 140000];
+        check::<256, 8, 32>(&ones, 140100);
+        check::<64, 4, 16>(&ones, 140100);
+        check::<64, 4, 16>(&[], 100);
+    }
+
+    #[test]
+    fn zero_bits() {
+        fn check<T: Fid>(fid: &T, bits: &[Bit]) {
+            let expected = LinearFid::new(bits.iter().cloned());
+            for i in 0..bits.len() as Index + 2 {
+                assert_eq!(fid.rank_zero(i), expected.rank_zero(i), "rank_zero({i})");
+                assert_eq!(
+                    fid.select_zero(i + 1),
+                    expected.select_zero(i + 1),
+                    "select_zero({})",
+                    i + 1
+                );
+                assert_eq!(fid.succ_zero(i), expected.succ_zero(i), "succ_zero({i})");
+                if i < bits.len() as Index {
+                    assert_eq!(fid.pred_zero(i), expected.pred_zero(i), "pred_zero({i})");
+                }
+            }
+            assert_eq!(fid.select_zero(0), None);
+        }
+
+        let patterns = [
+            (0..3000).map(|i| i % 5 == 0).collect::<Vec<_>>(),
+            (0..3000).map(|i| i % 3 != 0 && i < 2500).collect(),
+            (0..3000).map(|i| (300..700).contains(&i)).collect(),
+            (0..3000).map(|i| i % 700 == 699).collect(),
+            vec![false; 100],
+            vec![],
+        ];
+        for bits in &patterns {
+            check(&bits.iter().cloned().collect::<SparseOneNnd>(), bits);
+            check(
+                &bits
+                    .iter()
+                    .cloned()
+                    .collect::<SparseOneNnd<Owned, 64, 2, 4>>(),
+                bits,
+            );
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::io::{self, Read, Write};
 use std::iter;
 use std::mem;
 
@@ -9,10 +10,14 @@ use super::ops::{PredOne, PredZero, RankBit, SelectOne, SelectZero, SuccOne, Suc
 use super::Bit;
 use super::Index;
 use super::Rank;
+use crate::serialize::{self, FromBytes, Pod, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
+
+const MAGIC: &[u8; 4] = b"BSTR";
 
 #[derive(Debug, Clone)]
-pub struct BitString<N = u64> {
-    fixnums: Vec<Fixnum<N>>,
+pub struct BitString<N: FixnumLike = u64, S: Storage = Owned> {
+    fixnums: S::Slice<Fixnum<N>>,
     len: Index,
 }
 impl Default for BitString<u64> {
@@ -33,14 +38,6 @@ where
             len: 0,
         }
     }
-    pub fn get(&self, index: Index) -> Option<Bit> {
-        if index < self.len() {
-            let (base, offset) = Self::base_and_offset(index);
-            Some(unsafe { self.fixnums.get_unchecked(base) }.get(offset))
-        } else {
-            None
-        }
-    }
     pub fn resize(&mut self, size: Index) {
         let new_len = (size / N::bitwidth() as u64) as usize + 1;
         self.fixnums.resize(new_len, Fixnum::zero());
@@ -61,27 +58,41 @@ where
         }
         self.len += 1;
     }
+    pub fn shrink_to_fit(&mut self) {
+        self.fixnums.shrink_to_fit();
+    }
+    pub fn into_fixnums(self) -> Vec<Fixnum<N>> {
+        self.fixnums
+    }
+}
+impl<N, S> BitString<N, S>
+where
+    N: FixnumLike,
+    S: Storage,
+{
+    pub fn get(&self, index: Index) -> Option<Bit> {
+        if index < self.len() {
+            let (base, offset) = Self::base_and_offset(index);
+            Some(unsafe { self.fixnums.get_unchecked(base) }.get(offset))
+        } else {
+            None
+        }
+    }
     pub fn len(&self) -> Index {
         self.len
     }
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
-    pub fn shrink_to_fit(&mut self) {
-        self.fixnums.shrink_to_fit();
-    }
-    pub fn iter(&self) -> Iter<'_, N> {
+    pub fn iter(&self) -> Iter<'_, N, S> {
         Iter::new(self)
     }
-    pub fn one_indices(&self) -> OneIndices<'_, N> {
+    pub fn one_indices(&self) -> OneIndices<'_, N, S> {
         OneIndices::new(self)
     }
     pub fn as_fixnums(&self) -> &[Fixnum<N>] {
         &self.fixnums
     }
-    pub fn into_fixnums(self) -> Vec<Fixnum<N>> {
-        self.fixnums
-    }
 
     fn base_and_offset(index: Index) -> (usize, Index) {
         (
@@ -90,9 +101,10 @@ where
         )
     }
 }
-impl<N> RankBit for BitString<N>
+impl<N, S> RankBit for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn rank_one(&self, index: Index) -> Rank {
         let mut rank = 0;
@@ -109,9 +121,10 @@ where
         rank
     }
 }
-impl<N> SelectZero for BitString<N>
+impl<N, S> SelectZero for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn select_zero(&self, rank: Rank) -> Option<Index> {
         if rank == 0 {
@@ -141,9 +154,10 @@ where
         None
     }
 }
-impl<N> SelectOne for BitString<N>
+impl<N, S> SelectOne for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn select_one(&self, rank: Rank) -> Option<Index> {
         if rank == 0 {
@@ -165,33 +179,46 @@ where
         None
     }
 }
-impl<N> PredZero for BitString<N>
+impl<N, S> PredZero for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn pred_zero(&self, index: Index) -> Option<Index> {
         ops::naive_pred_zero(self, index)
     }
 }
-impl<N> PredOne for BitString<N>
+impl<N, S> PredOne for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn pred_one(&self, index: Index) -> Option<Index> {
         ops::naive_pred_one(self, index)
     }
 }
-impl<N> SuccZero for BitString<N>
+impl<N, S> SuccZero for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn succ_zero(&self, index: Index) -> Option<Index> {
-        ops::naive_succ_zero(self, index)
+        let (mut base, mut offset) = Self::base_and_offset(index);
+        while base < self.fixnums.len() {
+            if let Some(i) = self.fixnums[base].succ_zero(offset) {
+                let i = base as Index * N::bitwidth() as Index + i;
+                return Some(i).filter(|&i| i < self.len);
+            }
+            base += 1;
+            offset = 0;
+        }
+        None
     }
 }
-impl<N> SuccOne for BitString<N>
+impl<N, S> SuccOne for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn succ_one(&self, index: Index) -> Option<Index> {
         let (mut base, mut offset) = Self::base_and_offset(index);
@@ -205,12 +232,74 @@ where
         None
     }
 }
-impl<N> ops::ExternalByteSize for BitString<N> {
+impl<N: FixnumLike, S: Storage> ops::ExternalByteSize for BitString<N, S> {
     fn external_byte_size(&self) -> u64 {
         self.fixnums.len() as u64 * mem::size_of::<N>() as u64
     }
 }
 
+impl<N, S> WriteTo for BitString<N, S>
+where
+    N: FixnumLike + Pod,
+    S: Storage,
+{
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, MAGIC)?;
+        serialize::write_u64(writer, self.len)?;
+        serialize::write_u64(writer, N::bitwidth() as u64)?;
+        serialize::write_slice(writer, &self.fixnums)
+    }
+}
+impl<N> ReadFrom for BitString<N>
+where
+    N: FixnumLike + Pod,
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, MAGIC)?;
+        let len = read_len::<N, _>(&mut reader)?;
+        let fixnums = serialize::read_vec(&mut reader)?;
+        BitString { fixnums, len }.validate()
+    }
+}
+impl<'a, N> FromBytes<'a> for BitString<N, Borrowed<'a>>
+where
+    N: FixnumLike + Pod,
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, MAGIC)?;
+        let len = read_len::<N, _>(bytes)?;
+        let fixnums = serialize::view_slice(bytes)?;
+        BitString { fixnums, len }.validate()
+    }
+}
+impl<N, S> BitString<N, S>
+where
+    N: FixnumLike,
+    S: Storage,
+{
+    fn validate(self) -> io::Result<Self> {
+        if (self.fixnums.len() as u64) < self.len.div_ceil(N::bitwidth() as u64) {
+            return Err(serialize::invalid_data(format!(
+                "Too few fixnums: len={}, fixnums={}",
+                self.len,
+                self.fixnums.len()
+            )));
+        }
+        Ok(self)
+    }
+}
+fn read_len<N: FixnumLike, R: Read>(reader: &mut R) -> io::Result<Index> {
+    let len = serialize::read_u64(reader)?;
+    let bitwidth = serialize::read_u64(reader)?;
+    if bitwidth != N::bitwidth() as u64 {
+        return Err(serialize::invalid_data(format!(
+            "Unexpected fixnum bit width: expected={}, actual={bitwidth}",
+            N::bitwidth()
+        )));
+    }
+    Ok(len)
+}
+
 impl<N> iter::FromIterator<Bit> for BitString<N>
 where
     N: FixnumLike,
@@ -228,9 +317,10 @@ where
     }
 }
 
-impl<N> fmt::Display for BitString<N>
+impl<N, S> fmt::Display for BitString<N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for b in self.iter() {
@@ -240,18 +330,19 @@ where
     }
 }
 
-pub struct Iter<'a, N: 'a> {
-    bs: &'a BitString<N>,
+pub struct Iter<'a, N: FixnumLike, S: Storage = Owned> {
+    bs: &'a BitString<N, S>,
     i: Index,
 }
-impl<'a, N: 'a> Iter<'a, N> {
-    pub fn new(bs: &'a BitString<N>) -> Self {
+impl<'a, N: FixnumLike, S: Storage> Iter<'a, N, S> {
+    pub fn new(bs: &'a BitString<N, S>) -> Self {
         Iter { bs, i: 0 }
     }
 }
-impl<'a, N: 'a> Iterator for Iter<'a, N>
+impl<N, S> Iterator for Iter<'_, N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     type Item = Bit;
     fn next(&mut self) -> Option<Self::Item> {
@@ -260,18 +351,19 @@ where
     }
 }
 
-pub struct OneIndices<'a, N: 'a> {
-    bs: &'a BitString<N>,
+pub struct OneIndices<'a, N: FixnumLike, S: Storage = Owned> {
+    bs: &'a BitString<N, S>,
     i: Index,
 }
-impl<'a, N: 'a> OneIndices<'a, N> {
-    pub fn new(bs: &'a BitString<N>) -> Self {
+impl<'a, N: FixnumLike, S: Storage> OneIndices<'a, N, S> {
+    pub fn new(bs: &'a BitString<N, S>) -> Self {
         OneIndices { bs, i: 0 }
     }
 }
-impl<'a, N: 'a> Iterator for OneIndices<'a, N>
+impl<N, S> Iterator for OneIndices<'_, N, S>
 where
     N: FixnumLike,
+    S: Storage,
 {
     type Item = Index;
     fn next(&mut self) -> Option<Self::Item> {
@@ -282,11 +374,13 @@ where
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_conversion)]
 mod test {
     use super::super::ops::*;
     use super::super::{Index, Rank};
     use super::super::{ONE, ZERO};
     use super::*;
+    use crate::serialize::{ReadFrom, WriteTo};
 
     #[test]
     fn it_works() {
@@ -328,6 +422,29 @@ mod test {
         }
     }
 
+    #[test]
+    fn serialize() {
+        let bs = (0..1000).map(|i| i % 3 == 0).collect::<BitString<u16>>();
+        let mut buf = Vec::new();
+        bs.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len() % 8, 0);
+
+        let read = BitString::<u16>::read_from(&buf[..]).unwrap();
+        assert_eq!(read.len(), bs.len());
+        assert_eq!(read.as_fixnums(), bs.as_fixnums());
+
+        assert!(BitString::<u64>::read_from(&buf[..]).is_err());
+        assert!(BitString::<u16>::read_from(&buf[..buf.len() - 8]).is_err());
+
+        let aligned = serialize::aligned(&buf);
+        let mut bytes = serialize::aligned_bytes(&aligned);
+        let view = BitString::<u16, Borrowed>::from_bytes(&mut bytes).unwrap();
+        assert!(bytes.is_empty());
+        assert_eq!(view.as_fixnums(), bs.as_fixnums());
+        assert_eq!(view.to_string(), bs.to_string());
+        assert_eq!(view.rank_one(500), bs.rank_one(500));
+    }
+
     #[test]
     fn to_string() {
         let bits = [ZERO, ONE, ONE, ONE, ZERO, ONE, ZERO, ZERO, ONE, ZERO];
@@ -2,5 +2,7 @@
 pub use self::tree::balanced_parens::BalancedParensTree;
 
 pub mod bitwise;
+pub mod serialize;
+pub mod storage;
 pub mod tree;
 pub mod word;
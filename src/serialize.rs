@@ -0,0 +1,279 @@
+//! Binary serialization format.
+//!
+//! Every structure is encoded as an 8-byte header followed by its fields:
+//!
+//! - header: a 4-byte magic identifying the structure and the format version (`u32`)
+//! - integer field: a little-endian `u64`
+//! - array field: the number of elements (`u64`), the little-endian elements,
+//!   and zero padding up to the next multiple of 8 bytes
+//! - nested structure: its own header and fields
+//!
+//! As all the fields are multiples of 8 bytes, every array starts at an 8-byte aligned offset
+//! relative to the beginning of the encoded structure.
+//!
+//! | Structure            | Magic  | Fields                                                  |
+//! |----------------------|--------|---------------------------------------------------------|
+//! | `BitString`          | `BSTR` | bit length, fixnum bit width, fixnums (array)           |
+//! | `IndexedBitString`   | `IBST` | `BitString`, superblocks, blocks, one hints, zero hints |
+//! | `SparseOneNnd`       | `SONN` | block sizes, length, smalls, middles, larges (arrays)   |
+//! | `EliasFanoNnd`       | `EFNN` | length, low width, lows (array), `IndexedBitString`     |
+//! | `Parens`             | `PRNS` | `BitString`, pioneer flag, \[nnd, `Parens`\], rmM tree  |
+//! | range min-max tree   | `RMMT` | leaf excesses, node min/max/min-count triples (arrays)  |
+//! | `LabelVec`           | `LVEC` | labels (array)                                          |
+//! | `Letters`            | `LTRS` | end-of-word `IndexedBitString`, values (array)          |
+//! | `LettersWithValues`  | `LTRV` | `Letters`, word values (array)                          |
+//! | `PatriciaLetters`    | `PLTR` | end-of-word `BitString`, end offsets, values (arrays)   |
+//! | `BalancedParensTree` | `BPTR` | labels, `Parens`                                        |
+//!
+//! The nnd and pioneer `Parens` of `Parens` are present only if the pioneer flag is `1`.
+//!
+//! On little-endian targets, [`FromBytes`] views the arrays of an 8-byte aligned buffer
+//! (e.g. a memory-mapped file) in place instead of copying them.
+use std::io::{self, Read, Write};
+use std::mem;
+use std::slice;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+const ALIGNMENT: usize = 8;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+pub trait WriteTo {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+}
+
+pub trait ReadFrom: Sized {
+    fn read_from<R: Read>(reader: R) -> io::Result<Self>;
+}
+
+pub trait FromBytes<'a>: Sized {
+    /// Makes a structure borrowing the arrays of `bytes`, and advances `bytes` past it.
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self>;
+}
+
+/// Fixed-size values that can be stored in the array fields.
+///
+/// # Safety
+///
+/// Implementors must have no padding bytes and every bit pattern must be a valid value.
+pub unsafe trait Pod: Copy + 'static {
+    /// Converts between the native and the little-endian byte orders.
+    fn to_le(self) -> Self;
+}
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {
+            fn to_le(self) -> Self {
+                <$t>::to_le(self)
+            }
+        })*
+    };
+}
+impl_pod!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+pub(crate) fn write_header<W: Write>(writer: &mut W, magic: &[u8; 4]) -> io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())
+}
+
+pub(crate) fn read_header<R: Read>(reader: &mut R, magic: &[u8; 4]) -> io::Result<()> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != magic {
+        return Err(invalid_data(format!(
+            "Unexpected magic: expected={:?}, actual={:?}",
+            String::from_utf8_lossy(magic),
+            String::from_utf8_lossy(&header[..4])
+        )));
+    }
+    let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported format version: {version}"
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn write_u64<W: Write>(writer: &mut W, n: u64) -> io::Result<()> {
+    writer.write_all(&n.to_le_bytes())
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub(crate) fn write_slice<W: Write, T: Pod>(writer: &mut W, items: &[T]) -> io::Result<()> {
+    write_u64(writer, items.len() as u64)?;
+    if cfg!(target_endian = "little") {
+        writer.write_all(as_bytes(items))?;
+    } else {
+        for item in items {
+            writer.write_all(as_bytes(&[item.to_le()]))?;
+        }
+    }
+    writer.write_all(&[0; ALIGNMENT][..padding_size::<T>(items.len())])
+}
+
+pub(crate) fn read_vec<R: Read, T: Pod>(reader: &mut R) -> io::Result<Vec<T>> {
+    let len = read_u64(reader)? as usize;
+    let mut items = Vec::<T>::new();
+    while items.len() < len {
+        let start = items.len();
+        let end = start + (len - start).min(READ_CHUNK_SIZE);
+        // SAFETY: Every bit pattern is a valid `T` (see `Pod`).
+        items.resize(end, unsafe { mem::zeroed() });
+        let bytes = &mut items[start..end];
+        // SAFETY: `T` has no padding bytes.
+        let bytes = unsafe {
+            slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut u8, mem::size_of_val(bytes))
+        };
+        reader.read_exact(bytes)?;
+    }
+    if !cfg!(target_endian = "little") {
+        for item in &mut items {
+            *item = item.to_le();
+        }
+    }
+    let mut padding = [0; ALIGNMENT];
+    reader.read_exact(&mut padding[..padding_size::<T>(len)])?;
+    Ok(items)
+}
+
+pub(crate) fn view_slice<'a, T: Pod>(bytes: &mut &'a [u8]) -> io::Result<&'a [T]> {
+    if !cfg!(target_endian = "little") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Borrowing serialized arrays is only supported on little-endian targets",
+        ));
+    }
+    let len = read_u64(bytes)?;
+    if len > (bytes.len() / mem::size_of::<T>()) as u64 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let len = len as usize;
+    let size = len * mem::size_of::<T>();
+    if size + padding_size::<T>(len) > bytes.len() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    if !(bytes.as_ptr() as usize).is_multiple_of(mem::align_of::<T>()) {
+        return Err(invalid_data(
+            "Misaligned array (the buffer must be 8-byte aligned)",
+        ));
+    }
+    // SAFETY: The pointer is aligned, the range is in `bytes`, and every bit pattern is a valid `T`.
+    let items = unsafe { slice::from_raw_parts(bytes.as_ptr() as *const T, len) };
+    *bytes = &bytes[size + padding_size::<T>(len)..];
+    Ok(items)
+}
+
+pub(crate) fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn as_bytes<T: Pod>(items: &[T]) -> &[u8] {
+    // SAFETY: `T` has no padding bytes.
+    unsafe { slice::from_raw_parts(items.as_ptr() as *const u8, mem::size_of_val(items)) }
+}
+
+fn padding_size<T>(len: usize) -> usize {
+    let size = mem::size_of::<T>() * len;
+    (ALIGNMENT - size % ALIGNMENT) % ALIGNMENT
+}
+
+/// Copies serialized bytes into an 8-byte aligned buffer (see `aligned_bytes`).
+#[cfg(test)]
+pub(crate) fn aligned(buf: &[u8]) -> Vec<u64> {
+    assert_eq!(buf.len() % ALIGNMENT, 0);
+    buf.chunks(ALIGNMENT)
+        .map(|c| u64::from_ne_bytes(c.try_into().unwrap()))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) fn aligned_bytes(words: &[u64]) -> &[u8] {
+    as_bytes(words)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slice_roundtrip() {
+        let items = [1u16, 2, 3, 0xFFFF, 5];
+        let mut buf = Vec::new();
+        write_slice(&mut buf, &items).unwrap();
+        assert_eq!(buf.len(), 8 + 16);
+        assert_eq!(&buf[8..12], &[1, 0, 2, 0]);
+        assert_eq!(read_vec::<_, u16>(&mut &buf[..]).unwrap(), items);
+
+        let mut buf = Vec::new();
+        write_slice::<_, u64>(&mut buf, &[]).unwrap();
+        assert_eq!(buf.len(), 8);
+        assert!(read_vec::<_, u64>(&mut &buf[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn header() {
+        let mut buf = Vec::new();
+        write_header(&mut buf, b"TEST").unwrap();
+        assert_eq!(buf.len(), 8);
+        assert!(read_header(&mut &buf[..], b"TEST").is_ok());
+
+        let e = read_header(&mut &buf[..], b"FOO_").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        buf[4] += 1;
+        let e = read_header(&mut &buf[..], b"TEST").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn view_slice_in_place() {
+        let mut buf = Vec::new();
+        write_slice(&mut buf, &[1u32, 2, 3]).unwrap();
+        write_slice(&mut buf, &[4u64]).unwrap();
+        let words = aligned(&buf);
+        let aligned = aligned_bytes(&words);
+
+        let mut bytes = aligned;
+        assert_eq!(view_slice::<u32>(&mut bytes).unwrap(), [1, 2, 3]);
+        assert_eq!(view_slice::<u64>(&mut bytes).unwrap(), [4]);
+        assert!(bytes.is_empty());
+
+        let mut bytes = &aligned[..aligned.len() - 1];
+        view_slice::<u32>(&mut bytes).unwrap();
+        let e = view_slice::<u64>(&mut bytes).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn view_slice_huge_len() {
+        for len in [u64::MAX, u64::MAX - 3, u64::MAX / 4 + 1, 17] {
+            let mut buf = Vec::new();
+            write_u64(&mut buf, len).unwrap();
+            write_u64(&mut buf, 0).unwrap();
+            let words = aligned(&buf);
+
+            let e = view_slice::<u8>(&mut aligned_bytes(&words)).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+            let e = view_slice::<u32>(&mut aligned_bytes(&words)).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let mut buf = Vec::new();
+        write_slice(&mut buf, &[1u64, 2, 3]).unwrap();
+        buf.pop();
+        let e = read_vec::<_, u64>(&mut &buf[..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
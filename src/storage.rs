@@ -0,0 +1,23 @@
+//! Backing storage of the arrays held by the data structures.
+//!
+//! Structures built in memory use [`Owned`] storage, and the ones viewing serialized bytes
+//! (see [`FromBytes`](crate::serialize::FromBytes)) use [`Borrowed`] storage.
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+pub trait Storage {
+    type Slice<T: Clone + Debug + 'static>: Deref<Target = [T]> + Clone + Debug;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Owned;
+impl Storage for Owned {
+    type Slice<T: Clone + Debug + 'static> = Vec<T>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Borrowed<'a>(PhantomData<&'a [u8]>);
+impl<'a> Storage for Borrowed<'a> {
+    type Slice<T: Clone + Debug + 'static> = &'a [T];
+}
@@ -1,28 +1,42 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
+use std::ops::Range;
 use std::rc::Rc;
 
+use self::parentheses::Excess;
 use self::parentheses::Parens;
 use super::Edge;
 use super::LabelVec;
 use super::Labels;
+use super::LabelsMut;
 use super::NodeId;
 use crate::bitwise::ops::ExternalByteSize;
 use crate::bitwise::ops::NndOne;
+use crate::bitwise::ops::RankBit;
+use crate::bitwise::ops::SelectOne;
 use crate::bitwise::BitString;
 use crate::bitwise::Index;
+use crate::bitwise::Rank;
 use crate::bitwise::SparseOneNnd;
+use crate::serialize::{self, FromBytes, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
 use crate::tree::traversal::DepthFirstIter;
 use crate::tree::traversal::DepthFirstTraverse;
 
-mod parentheses;
+pub mod parentheses;
+mod range_min_max;
 
-pub struct BalancedParensTree<L, N = SparseOneNnd> {
+const MAGIC: &[u8; 4] = b"BPTR";
+
+pub struct BalancedParensTree<L, N = SparseOneNnd, S: Storage = Owned> {
     labels: L,
-    parens: Parens<N>,
+    parens: Parens<N, S>,
 }
 impl<L> BalancedParensTree<LabelVec<L>, SparseOneNnd>
 where
-    L: Clone,
+    L: Clone + Debug,
 {
     pub fn new<T>(tree: T) -> Self
     where
@@ -33,7 +47,7 @@ where
 }
 impl<L, N> BalancedParensTree<L, N>
 where
-    L: Labels,
+    L: LabelsMut,
     N: NndOne + From<BitString>,
 {
     pub fn new_builder<T>(tree: T, labels: L) -> Builder<T, L, N>
@@ -43,19 +57,21 @@ where
         Builder::new(tree, labels)
     }
 }
-impl<L, N> BalancedParensTree<L, N>
+impl<L, N, S> BalancedParensTree<L, N, S>
 where
     L: ExternalByteSize,
     N: ExternalByteSize,
+    S: Storage,
 {
     pub fn external_byte_size(&self) -> u64 {
         self.labels.external_byte_size() + self.parens.external_byte_size()
     }
 }
-impl<L, N> BalancedParensTree<L, N>
+impl<L, N, S> BalancedParensTree<L, N, S>
 where
     L: Labels,
     N: NndOne,
+    S: Storage,
 {
     pub fn root(&self) -> Node<L, N, &Self> {
         Node::new(0, 0, self)
@@ -63,11 +79,53 @@ where
     pub fn to_owned_root(self) -> Node<L, N, Rc<Self>> {
         Node::new(0, 0, Rc::new(self))
     }
+    pub fn node(&self, id: NodeId) -> Option<Node<L, N, &Self>> {
+        let open = self.parens.select_one(id as Rank + 1)?;
+        Some(Node::new(open as NodeId, id, self))
+    }
 }
 
-impl<L, N> BalancedParensTree<L, N>
+impl<L, N, S> WriteTo for BalancedParensTree<L, N, S>
+where
+    L: WriteTo,
+    N: WriteTo,
+    S: Storage,
+{
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, MAGIC)?;
+        self.labels.write_to(writer)?;
+        self.parens.write_to(writer)
+    }
+}
+impl<L, N> ReadFrom for BalancedParensTree<L, N>
+where
+    L: ReadFrom + Labels,
+    N: ReadFrom,
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, MAGIC)?;
+        let labels = L::read_from(&mut reader)?;
+        let parens = Parens::read_from(&mut reader)?;
+        BalancedParensTree { labels, parens }.validate()
+    }
+}
+impl<'a, L, N> FromBytes<'a> for BalancedParensTree<L, N, Borrowed<'a>>
+where
+    L: FromBytes<'a> + Labels,
+    N: FromBytes<'a>,
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, MAGIC)?;
+        let labels = L::from_bytes(bytes)?;
+        let parens = Parens::from_bytes(bytes)?;
+        BalancedParensTree { labels, parens }.validate()
+    }
+}
+
+impl<L, N, S> BalancedParensTree<L, N, S>
 where
     L: Labels,
+    S: Storage,
 {
     pub fn len(&self) -> usize {
         self.labels.len()
@@ -75,11 +133,73 @@ where
     pub fn is_empty(&self) -> bool {
         self.labels.len() == 0
     }
+
+    fn validate(self) -> io::Result<Self> {
+        if self.parens.len() != (self.labels.len() as Index + 1) * 2 {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected parentheses length: labels={}, parens={}",
+                self.labels.len(),
+                self.parens.len()
+            )));
+        }
+        Ok(self)
+    }
 }
-impl<L, N> BalancedParensTree<L, N> {
+impl<L, N, S: Storage> BalancedParensTree<L, N, S> {
     pub fn labels(&self) -> &L {
         &self.labels
     }
+    pub fn parens(&self) -> &Parens<N, S> {
+        &self.parens
+    }
+
+    /// Returns the depth of the node (the root is at depth `0`), or `None` if there is no such node.
+    pub fn depth(&self, id: NodeId) -> Option<usize> {
+        Some((self.parens.excess(self.position(id)?) - 1) as usize)
+    }
+
+    /// Returns the ancestor `distance` levels above the node (the node itself if `distance` is `0`).
+    pub fn level_ancestor(&self, id: NodeId, distance: usize) -> Option<NodeId> {
+        let position = self.position(id)?;
+        if distance >= self.parens.excess(position) as usize {
+            // Above the root (this also keeps `distance` in the range of `Excess`).
+            return None;
+        }
+        let open = self
+            .parens
+            .bwd_search(position, -(distance as Excess) - 1)?;
+        Some(self.node_id(open))
+    }
+
+    /// Returns the lowest common ancestor of the two nodes, or `None` if either does not exist.
+    pub fn lca(&self, a: NodeId, b: NodeId) -> Option<NodeId> {
+        let (a, b) = (a.min(b), a.max(b));
+        let (x, y) = (self.position(a)?, self.position(b)?);
+        if y <= self.parens.find_close(x).unwrap() {
+            return Some(a);
+        }
+        // The leftmost minimum in `x..=y` closes the child of the LCA that contains `a`.
+        let min = self.parens.rmq_index(x, y);
+        Some(self.node_id(self.parens.enclose(min + 1).unwrap()))
+    }
+
+    /// Replaces the labels, which must have the same length, by `f(labels)`.
+    pub(crate) fn try_map_labels<M, E, F>(self, f: F) -> Result<BalancedParensTree<M, N, S>, E>
+    where
+        F: FnOnce(L) -> Result<M, E>,
+    {
+        Ok(BalancedParensTree {
+            labels: f(self.labels)?,
+            parens: self.parens,
+        })
+    }
+
+    fn position(&self, id: NodeId) -> Option<Index> {
+        self.parens.select_one(id as Rank + 1)
+    }
+    fn node_id(&self, open: Index) -> NodeId {
+        (self.parens.rank_one(open) - 1) as NodeId
+    }
 }
 
 pub struct Builder<T, L, N = SparseOneNnd> {
@@ -92,7 +212,7 @@ pub struct Builder<T, L, N = SparseOneNnd> {
 impl<T, L, N> Builder<T, L, N>
 where
     T: DepthFirstTraverse,
-    L: Labels<Label = T::Label>,
+    L: LabelsMut<Label = T::Label>,
     N: NndOne + From<BitString>,
 {
     pub fn new(tree: T, labels: L) -> Self {
@@ -147,11 +267,12 @@ pub struct Node<L, N, T> {
     _n: PhantomData<N>,
     _l: PhantomData<L>,
 }
-impl<L, N, T> Node<L, N, T>
+impl<L, N, S, T> Node<L, N, T>
 where
     L: Labels,
     N: NndOne,
-    T: ::std::ops::Deref<Target = BalancedParensTree<L, N>> + Clone,
+    S: Storage,
+    T: ::std::ops::Deref<Target = BalancedParensTree<L, N, S>> + Clone,
 {
     fn new(inner_id: NodeId, id: NodeId, tree: T) -> Self {
         Node {
@@ -163,6 +284,120 @@ where
         }
     }
 }
+impl<L, N, S, T> Node<L, N, T>
+where
+    L: Labels,
+    N: NndOne,
+    S: Storage,
+    T: ::std::ops::Deref<Target = BalancedParensTree<L, N, S>> + Clone,
+{
+    pub fn is_root(&self) -> bool {
+        self.id == 0
+    }
+    pub fn label(&self) -> Option<L::Label> {
+        self.id
+            .checked_sub(1)
+            .and_then(|i| self.tree.labels.get(i as usize))
+    }
+    pub fn parent(&self) -> Option<Self> {
+        let open = self.tree.parens.enclose(self.inner_id as Index)?;
+        let id = self.tree.parens.rank_one(open) - 1;
+        Some(Self::new(open as NodeId, id as NodeId, self.tree.clone()))
+    }
+    pub fn ancestors(&self) -> Ancestors<L, N, T> {
+        Ancestors {
+            node: self.parent(),
+        }
+    }
+    pub fn is_leaf(&self) -> bool {
+        !self.tree.parens.get(self.inner_id as Index + 1).unwrap()
+    }
+
+    /// Returns the number of nodes in the subtree rooted at this node (including itself).
+    pub fn subtree_size(&self) -> usize {
+        let close = self.tree.parens.get_close(self.inner_id as Index).unwrap();
+        (close - self.inner_id as Index + 1) as usize / 2
+    }
+
+    /// Returns the number of children.
+    pub fn degree(&self) -> usize {
+        if self.is_leaf() {
+            return 0;
+        }
+        let close = self.tree.parens.get_close(self.inner_id as Index).unwrap();
+        self.tree
+            .parens
+            .min_count(self.inner_id as Index + 1, close - 1) as usize
+    }
+
+    /// Returns the `k`-th (from 0) child.
+    pub fn child(&self, k: usize) -> Option<Self> {
+        if self.is_leaf() {
+            return None;
+        }
+        let open = self.inner_id as Index;
+        let child = if k == 0 {
+            open + 1
+        } else {
+            let close = self.tree.parens.get_close(open).unwrap();
+            self.child_open(close, k)?
+        };
+        Some(self.child_at(child))
+    }
+
+    // Returns the open parenthesis of the `k`-th (`k > 0`) child, where `close` closes this node.
+    fn child_open(&self, close: Index, k: usize) -> Option<Index> {
+        // The children are closed at the minimum excess positions inside this node.
+        let open = self.inner_id as Index;
+        let next = self.tree.parens.min_select(open + 1, close - 1, k as u64)? + 1;
+        Some(next).filter(|&next| next != close)
+    }
+
+    // Returns the id of the child whose open parenthesis is at `child`.
+    fn child_id(&self, child: Index) -> NodeId {
+        self.id + (child - self.inner_id as Index + 1) as NodeId / 2
+    }
+    fn child_at(&self, child: Index) -> Self {
+        Self::new(child as NodeId, self.child_id(child), self.tree.clone())
+    }
+    pub fn last_child(&self) -> Option<Self> {
+        if self.is_leaf() {
+            return None;
+        }
+        let close = self.tree.parens.get_close(self.inner_id as Index).unwrap();
+        let open = self.tree.parens.get_open(close - 1).unwrap();
+        Some(self.node_at(open))
+    }
+    pub fn prev_sibling(&self) -> Option<Self> {
+        let prev = (self.inner_id as Index).checked_sub(1)?;
+        if self.tree.parens.get(prev).unwrap() {
+            // The parent.
+            return None;
+        }
+        let open = self.tree.parens.get_open(prev).unwrap();
+        Some(self.node_at(open))
+    }
+    fn node_at(&self, open: Index) -> Self {
+        let id = self.tree.node_id(open);
+        Self::new(open as NodeId, id, self.tree.clone())
+    }
+
+    /// Returns the number of preceding siblings (`0` for the root).
+    pub fn child_rank(&self) -> usize {
+        let open = self.inner_id as Index;
+        match self.tree.parens.enclose(open) {
+            Some(parent) if parent + 1 < open => {
+                self.tree.parens.min_count(parent + 1, open - 1) as usize
+            }
+            _ => 0,
+        }
+    }
+
+    /// Returns the ids of the descendants, which are contiguous in preorder.
+    pub fn descendants(&self) -> Range<NodeId> {
+        self.id + 1..self.id + self.subtree_size() as NodeId
+    }
+}
 impl<L, N, T> Node<L, N, T>
 where
     T: Clone,
@@ -172,11 +407,12 @@ where
     }
 }
 
-impl<L, N, T> super::Node<L::Label> for Node<L, N, T>
+impl<L, N, S, T> super::Node<L::Label> for Node<L, N, T>
 where
     L: Labels,
     N: NndOne,
-    T: ::std::ops::Deref<Target = BalancedParensTree<L, N>> + Clone,
+    S: Storage,
+    T: ::std::ops::Deref<Target = BalancedParensTree<L, N, S>> + Clone,
 {
     fn id(&self) -> NodeId {
         self.id
@@ -207,13 +443,84 @@ where
             None
         }
     }
+    fn lower_bound_child<M, F>(&self, label: &M, f: F) -> Option<Edge<L::Label, Self>>
+    where
+        F: Fn(&M, &L::Label) -> Ordering,
+    {
+        if self.is_leaf() {
+            return None;
+        }
+        // The labels are stored in preorder, so a probe only needs the position of the child.
+        let open = self.inner_id as Index;
+        let close = self.tree.parens.get_close(open).unwrap();
+        let degree = self.tree.parens.min_count(open + 1, close - 1) as usize;
+        let child_open = |k| match k {
+            0 => open + 1,
+            _ => self.child_open(close, k).unwrap(),
+        };
+        let labels = &self.tree.labels;
+        let (Ok(k) | Err(k)) = super::binary_search_by(degree, |k| {
+            let label_index = self.child_id(child_open(k)) as usize - 1;
+            f(label, &labels.get(label_index).unwrap()).reverse()
+        });
+        if k == degree {
+            return None;
+        }
+        let child = self.child_at(child_open(k));
+        Some(Edge::new(child.label().unwrap(), child))
+    }
+}
+
+impl<L, N, S, T> super::ReverseNode<L::Label> for Node<L, N, T>
+where
+    L: Labels,
+    N: NndOne,
+    S: Storage,
+    T: ::std::ops::Deref<Target = BalancedParensTree<L, N, S>> + Clone,
+{
+    fn last_child(&self) -> Option<Edge<L::Label, Self>> {
+        let child = Node::last_child(self)?;
+        Some(Edge::new(child.label().unwrap(), child))
+    }
+    fn prev_sibling(&self) -> Option<Edge<L::Label, Self>> {
+        let sibling = Node::prev_sibling(self)?;
+        Some(Edge::new(sibling.label().unwrap(), sibling))
+    }
+}
+
+pub struct Ancestors<L, N, T> {
+    node: Option<Node<L, N, T>>,
+}
+impl<L, N, S, T> Iterator for Ancestors<L, N, T>
+where
+    L: Labels,
+    N: NndOne,
+    S: Storage,
+    T: ::std::ops::Deref<Target = BalancedParensTree<L, N, S>> + Clone,
+{
+    type Item = Node<L, N, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.node.take()?;
+        self.node = node.parent();
+        Some(node)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::BalancedParensTree;
+    use crate::bitwise::EliasFanoNnd;
+    use crate::bitwise::IndexedBitString;
+    use crate::bitwise::SparseOneNnd;
+    use crate::serialize::{self, FromBytes, ReadFrom, WriteTo};
+    use crate::storage::Borrowed;
     use crate::tree::traversal::ByteLines;
-    use crate::word::Words;
+    use crate::tree::traversal::{numeric_words, word_lines};
+    use crate::tree::LabelVec;
+    use crate::tree::Labels;
+    use crate::tree::LabelsMut;
+    use crate::tree::Node;
+    use crate::word::{Letters, Words};
     use std::io;
 
     #[test]
@@ -240,4 +547,451 @@ mod test {
             ["aaa111222", "abc3344", "d"]
         );
     }
+
+    #[test]
+    fn indexed_bit_string_nnd() {
+        let words = numeric_words(500, 1);
+        let tree = BalancedParensTree::<_, IndexedBitString>::new_builder(
+            word_lines(&words),
+            LabelVec::new(),
+        )
+        .build_all();
+        assert_eq!(
+            Words::new(tree.root())
+                .map(|b| String::from_utf8(b).unwrap())
+                .collect::<Vec<_>>(),
+            words
+        );
+
+        // Only the parentheses of the root.
+        let tree = BalancedParensTree::<_, IndexedBitString>::new_builder(
+            word_lines::<&str>(&[]),
+            LabelVec::new(),
+        )
+        .build_all();
+        assert_eq!(tree.parens().len(), 2);
+        assert_eq!(tree.root().children().count(), 0);
+
+        // The opening and closing parentheses are far apart.
+        let word = "a".repeat(3000);
+        let tree = BalancedParensTree::<_, IndexedBitString>::new_builder(
+            word_lines(&[&word]),
+            LabelVec::new(),
+        )
+        .build_all();
+        let leaf = tree.root().find_path(word.bytes(), |a, b| *a == b.value);
+        assert_eq!(leaf.unwrap().ancestors().count(), 3000);
+        assert_eq!(Words::new(tree.root()).next(), Some(word.into_bytes()));
+    }
+
+    #[test]
+    fn elias_fano_nnd() {
+        let words = numeric_words(3000, 1);
+        let tree =
+            BalancedParensTree::<_, EliasFanoNnd>::new_builder(word_lines(&words), LabelVec::new())
+                .build_all();
+        assert_eq!(
+            Words::new(tree.root())
+                .map(|b| String::from_utf8(b).unwrap())
+                .collect::<Vec<_>>(),
+            words
+        );
+
+        // Only the parentheses of the root.
+        let tree = BalancedParensTree::<_, EliasFanoNnd>::new_builder(
+            word_lines::<&str>(&[]),
+            LabelVec::new(),
+        )
+        .build_all();
+        assert_eq!(tree.parens().len(), 2);
+        assert!(tree.root().parent().is_none());
+        assert_eq!(tree.root().subtree_size(), 1);
+
+        // A deep path, a wide root and words that prefix each other agree with the default index.
+        let deep = "a".repeat(3000);
+        let wide = (0..=255u8)
+            .map(|b| format!("{}", b as char))
+            .collect::<Vec<_>>();
+        for words in [
+            vec![deep],
+            wide,
+            vec!["1".into(), "10".into(), "100".into()],
+        ] {
+            let expected = BalancedParensTree::new(word_lines(&words));
+            let tree = BalancedParensTree::<_, EliasFanoNnd>::new_builder(
+                word_lines(&words),
+                LabelVec::new(),
+            )
+            .build_all();
+            assert_eq!(tree.len(), expected.len());
+            for id in tree.root().descendants() {
+                let (node, expected) = (tree.node(id).unwrap(), expected.node(id).unwrap());
+                assert_eq!(
+                    node.parent().map(|n| n.id()),
+                    expected.parent().map(|n| n.id())
+                );
+                assert_eq!(node.subtree_size(), expected.subtree_size());
+                assert_eq!(node.degree(), expected.degree());
+            }
+        }
+    }
+
+    #[test]
+    fn parent() {
+        let words = numeric_words(500, 1);
+        let tree = BalancedParensTree::new(word_lines(&words));
+        assert!(tree.root().parent().is_none());
+
+        for word in &words {
+            let node = tree
+                .root()
+                .find_path(word.bytes(), |a, b| *a == b.value)
+                .unwrap();
+            let mut labels = vec![node.label().unwrap().value];
+            labels.extend(node.ancestors().filter_map(|n| n.label()).map(|l| l.value));
+            labels.reverse();
+            assert_eq!(labels, word.as_bytes());
+
+            let ancestors = node.ancestors().collect::<Vec<_>>();
+            assert_eq!(ancestors.len(), word.len());
+            assert!(ancestors.last().unwrap().is_root());
+            assert_eq!(
+                node.parent().map(|n| n.id()),
+                ancestors.first().map(|n| n.id())
+            );
+        }
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert!(tree.root().parent().is_none());
+        assert_eq!(tree.root().ancestors().count(), 0);
+
+        // The parent of "abc" is the end of the word "ab".
+        let tree = BalancedParensTree::new(word_lines(&["ab", "abc"]));
+        let node = tree.root().find_path("abc".bytes(), |a, b| *a == b.value);
+        let parent = node.unwrap().parent().unwrap();
+        assert!(parent.label().unwrap().end_of_word);
+        assert_eq!(
+            parent.ancestors().map(|n| n.id()).collect::<Vec<_>>(),
+            [1, 0]
+        );
+    }
+
+    #[test]
+    fn subtree_size() {
+        let words = numeric_words(500, 1);
+        let tree = BalancedParensTree::new(word_lines(&words));
+        assert_eq!(tree.root().subtree_size(), tree.len() + 1);
+        assert_eq!(tree.root().descendants(), 1..tree.len() as u32 + 1);
+
+        for prefix in ["1", "12", "3", "499", "7"] {
+            let node = tree
+                .root()
+                .find_path(prefix.bytes(), |a, b| *a == b.value)
+                .unwrap();
+            let count = node
+                .descendants()
+                .chain(Some(node.id()))
+                .filter(|&id| tree.labels().get(id as usize - 1).unwrap().end_of_word)
+                .count();
+            assert_eq!(
+                count,
+                words.iter().filter(|w| w.starts_with(prefix)).count()
+            );
+            assert_eq!(node.subtree_size(), node.descendants().len() + 1);
+            assert_eq!(node.is_leaf(), node.subtree_size() == 1);
+        }
+        let leaf = tree.root().find_path("499".bytes(), |a, b| *a == b.value);
+        assert!(leaf.unwrap().is_leaf());
+        assert!(!tree.root().is_leaf());
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert_eq!(tree.root().subtree_size(), 1);
+        assert!(tree.root().descendants().is_empty());
+        assert!(tree.root().is_leaf());
+
+        // A word that is a prefix of another word is not a leaf.
+        let tree = BalancedParensTree::new(word_lines(&["ab", "abc"]));
+        let node = tree.root().find_path("ab".bytes(), |a, b| *a == b.value);
+        let node = node.unwrap();
+        assert!(!node.is_leaf());
+        assert_eq!(node.subtree_size(), 2);
+        assert_eq!(node.descendants(), 3..4);
+    }
+
+    #[test]
+    fn depth_and_lca() {
+        let words = numeric_words(500, 7);
+        let tree = BalancedParensTree::new(word_lines(&words));
+        let id = |word: &str| {
+            tree.root()
+                .find_path(word.bytes(), |a, b| *a == b.value)
+                .map_or(0, |n| n.id())
+        };
+        assert_eq!(tree.depth(0), Some(0));
+        assert_eq!(tree.level_ancestor(0, 1), None);
+
+        let missing = tree.len() as u32 + 1;
+        assert_eq!(tree.depth(missing), None);
+        assert_eq!(tree.level_ancestor(missing, 0), None);
+        assert_eq!(tree.level_ancestor(0, usize::MAX), None);
+        assert_eq!(tree.level_ancestor(3, 1 << 63), None);
+        assert_eq!(tree.level_ancestor(3, usize::MAX), None);
+        assert_eq!(tree.lca(0, missing), None);
+        assert_eq!(tree.lca(missing, 0), None);
+
+        for (i, word) in words.iter().enumerate() {
+            let node = id(word);
+            assert_eq!(tree.depth(node), Some(word.len()));
+            for distance in 0..=word.len() {
+                let ancestor = id(&word[..word.len() - distance]);
+                assert_eq!(tree.level_ancestor(node, distance), Some(ancestor));
+            }
+            assert_eq!(tree.level_ancestor(node, word.len() + 1), None);
+
+            for other in words.iter().skip(i).step_by(37) {
+                let common = word
+                    .bytes()
+                    .zip(other.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                let expected = id(&word[..common]);
+                assert_eq!(tree.lca(node, id(other)), Some(expected));
+                assert_eq!(tree.lca(id(other), node), Some(expected));
+            }
+        }
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert_eq!(tree.depth(0), Some(0));
+        assert_eq!(tree.depth(1), None);
+        assert_eq!(tree.level_ancestor(0, 0), Some(0));
+        assert_eq!(tree.lca(0, 0), Some(0));
+        assert_eq!(tree.lca(0, 1), None);
+        assert_eq!(tree.lca(u32::MAX, u32::MAX), None);
+    }
+
+    #[test]
+    fn node() {
+        let words = numeric_words(500, 1);
+        let tree = BalancedParensTree::new(word_lines(&words));
+        assert!(tree.node(0).unwrap().is_root());
+        assert!(tree.node(tree.len() as u32 + 1).is_none());
+
+        for word in &words {
+            let node = tree
+                .root()
+                .find_path(word.bytes(), |a, b| *a == b.value)
+                .unwrap();
+            let found = tree.node(node.id()).unwrap();
+            assert_eq!(found.id(), node.id());
+            assert_eq!(found.label().unwrap().value, node.label().unwrap().value);
+            assert_eq!(found.subtree_size(), node.subtree_size());
+            assert_eq!(
+                found.parent().map(|n| n.id()),
+                node.parent().map(|n| n.id())
+            );
+        }
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert!(tree.node(0).unwrap().is_root());
+        assert!(tree.node(1).is_none());
+        assert!(tree.node(u32::MAX).is_none());
+
+        // The ids of a single word are its depths.
+        let tree = BalancedParensTree::new(word_lines(&["abc"]));
+        for (id, c) in (1..).zip("abc".bytes()) {
+            assert_eq!(tree.node(id).unwrap().label().unwrap().value, c);
+        }
+        assert!(tree.node(4).is_none());
+    }
+
+    #[test]
+    fn child() {
+        let words = numeric_words(3000, 13);
+        let tree = BalancedParensTree::new(word_lines(&words));
+
+        for id in (0..tree.len() as u32 + 1).step_by(7) {
+            let node = tree.node(id).unwrap();
+            let children = node.children().map(|e| e.node.id()).collect::<Vec<_>>();
+            assert_eq!(node.degree(), children.len());
+            for (k, &child) in children.iter().enumerate() {
+                let c = node.child(k).unwrap();
+                assert_eq!(c.id(), child);
+                assert_eq!(c.child_rank(), k);
+                assert_eq!(
+                    c.prev_sibling().map(|n| n.id()),
+                    k.checked_sub(1).map(|k| children[k])
+                );
+            }
+            assert_eq!(node.last_child().map(|n| n.id()), children.last().copied());
+            assert!(node.child(children.len()).is_none());
+            assert!(node.child(children.len() + 10).is_none());
+        }
+        assert_eq!(tree.root().child_rank(), 0);
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        let root = tree.root();
+        assert_eq!(root.degree(), 0);
+        assert!(root.child(0).is_none());
+        assert!(root.child(usize::MAX).is_none());
+        assert!(root.last_child().is_none());
+
+        // Only the first child has no previous sibling.
+        let tree = BalancedParensTree::new(word_lines(&["a", "b", "c"]));
+        let root = tree.root();
+        assert_eq!(root.degree(), 3);
+        assert!(root.child(0).unwrap().prev_sibling().is_none());
+        assert_eq!(root.last_child().unwrap().child_rank(), 2);
+        assert!(root.child(0).unwrap().child(0).is_none());
+    }
+
+    #[test]
+    fn find_path_sorted() {
+        let words = numeric_words(3000, 13);
+        let tree = BalancedParensTree::new(word_lines(&words));
+        let root = tree.root();
+
+        for word in words.iter().step_by(7) {
+            let expected = root.find_path(word.bytes(), |a, b| *a == b.value);
+            let node = root.find_path_sorted(word.bytes(), |a, b| a.cmp(&b.value));
+            assert_eq!(node.as_ref().map(|n| n.id()), expected.map(|n| n.id()));
+            assert!(node.unwrap().label().unwrap().end_of_word);
+        }
+        for word in ["x", "12a", "39000", ""] {
+            let expected = root.find_path(word.bytes(), |a, b| *a == b.value);
+            let node = root.find_path_sorted(word.bytes(), |a, b| a.cmp(&b.value));
+            assert_eq!(node.map(|n| n.id()), expected.map(|n| n.id()));
+        }
+        let node = root.find_child_by_label(&b'1', |a, b| a.cmp(&b.value));
+        assert_eq!(node.unwrap().label.value, b'1');
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert!(tree
+            .root()
+            .find_child_by_label(&b'a', |a, b| a.cmp(&b.value))
+            .is_none());
+
+        // Labels before, between and after the children, and past a leaf.
+        let tree = BalancedParensTree::new(word_lines(&["b", "d", "f"]));
+        let root = tree.root();
+        for (c, expected) in [(b'a', None), (b'b', Some(1)), (b'c', None), (b'f', Some(3))] {
+            let child = root.find_child_by_label(&c, |a, b| a.cmp(&b.value));
+            assert_eq!(child.map(|e| e.node.id()), expected);
+        }
+        assert!(root
+            .find_child_by_label(&b'g', |a, b| a.cmp(&b.value))
+            .is_none());
+        assert!(root
+            .find_path_sorted("fa".bytes(), |a, b| a.cmp(&b.value))
+            .is_none());
+    }
+
+    #[test]
+    fn serialize() {
+        let words = numeric_words(500, 1);
+        let tree = BalancedParensTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new())
+            .build_all();
+
+        let mut buf = Vec::new();
+        tree.write_to(&mut buf).unwrap();
+        let read = BalancedParensTree::<Letters<u8>, SparseOneNnd>::read_from(&buf[..]).unwrap();
+        assert_eq!(read.len(), tree.len());
+        assert_eq!(read.external_byte_size(), tree.external_byte_size());
+        assert_eq!(
+            Words::new(read.root())
+                .map(|b| String::from_utf8(b).unwrap())
+                .collect::<Vec<_>>(),
+            words
+        );
+
+        let mut labels = LabelVec::new();
+        labels.push(3u32);
+        labels.push(4);
+        let mut buf = Vec::new();
+        labels.write_to(&mut buf).unwrap();
+        let read = LabelVec::<u32>::read_from(&buf[..]).unwrap();
+        assert_eq!(read.get(1), Some(4));
+
+        assert!(BalancedParensTree::<Letters<u8>, IndexedBitString>::read_from(&buf[..]).is_err());
+
+        let empty =
+            BalancedParensTree::<_>::new_builder(word_lines::<&str>(&[]), Letters::<u8>::new())
+                .build_all();
+        let mut buf = Vec::new();
+        empty.write_to(&mut buf).unwrap();
+        let read = BalancedParensTree::<Letters<u8>, SparseOneNnd>::read_from(&buf[..]).unwrap();
+        assert!(read.is_empty());
+        assert_eq!(read.root().children().count(), 0);
+
+        let e = BalancedParensTree::<Letters<u8>, SparseOneNnd>::read_from(&buf[..buf.len() - 1])
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        // The labels and the parentheses of different trees.
+        let mixed = BalancedParensTree {
+            labels: tree.labels().clone(),
+            parens: empty.parens,
+        };
+        let mut buf = Vec::new();
+        mixed.write_to(&mut buf).unwrap();
+        let e = BalancedParensTree::<Letters<u8>, SparseOneNnd>::read_from(&buf[..])
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn from_bytes() {
+        type View<'a> =
+            BalancedParensTree<Letters<u8, Borrowed<'a>>, SparseOneNnd<Borrowed<'a>>, Borrowed<'a>>;
+
+        let words = numeric_words(2000, 1);
+        let tree = BalancedParensTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new())
+            .build_all();
+        let mut buf = Vec::new();
+        tree.write_to(&mut buf).unwrap();
+
+        let aligned = serialize::aligned(&buf);
+        let mut bytes = serialize::aligned_bytes(&aligned);
+        let view = View::from_bytes(&mut bytes).unwrap();
+        assert!(bytes.is_empty());
+        assert_eq!(view.len(), tree.len());
+        assert_eq!(view.external_byte_size(), tree.external_byte_size());
+        assert_eq!(
+            Words::new(view.root())
+                .map(|b| String::from_utf8(b).unwrap())
+                .collect::<Vec<_>>(),
+            words
+        );
+
+        let mut copied = Vec::new();
+        view.write_to(&mut copied).unwrap();
+        assert_eq!(copied, buf);
+
+        let mut bytes = &serialize::aligned_bytes(&aligned)[..buf.len() - 8];
+        let e = View::from_bytes(&mut bytes).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        // Two trees in a row, the first of which has only the root.
+        let empty =
+            BalancedParensTree::<_>::new_builder(word_lines::<&str>(&[]), Letters::<u8>::new())
+                .build_all();
+        let mut buf = Vec::new();
+        empty.write_to(&mut buf).unwrap();
+        tree.write_to(&mut buf).unwrap();
+        let aligned = serialize::aligned(&buf);
+        let mut bytes = serialize::aligned_bytes(&aligned);
+        assert!(View::from_bytes(&mut bytes).unwrap().is_empty());
+        assert_eq!(View::from_bytes(&mut bytes).unwrap().len(), tree.len());
+        assert!(bytes.is_empty());
+
+        let mut shifted = vec![0; 4];
+        shifted.extend_from_slice(&buf);
+        shifted.extend_from_slice(&[0; 4]);
+        let aligned = serialize::aligned(&shifted);
+        let mut bytes = &serialize::aligned_bytes(&aligned)[4..];
+        let e = View::from_bytes(&mut bytes).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("Misaligned"));
+    }
 }
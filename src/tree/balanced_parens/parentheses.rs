@@ -1,15 +1,31 @@
+use std::io::{self, Read, Write};
+
 use crate::bitwise;
 use crate::bitwise::fixnum::Fixnum;
 use crate::bitwise::fixnum::FixnumLike;
 use crate::bitwise::ops::ExternalByteSize;
 use crate::bitwise::ops::GetClose;
+use crate::bitwise::ops::GetOpen;
 use crate::bitwise::ops::NndOne;
+use crate::bitwise::ops::RankBit;
+use crate::bitwise::ops::SelectOne;
+use crate::bitwise::ops::SelectZero;
+use crate::bitwise::ops::SuccZero;
 use crate::bitwise::Bit;
 use crate::bitwise::Index;
+use crate::bitwise::Rank;
+use crate::serialize::{self, FromBytes, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
+
+use super::range_min_max::RangeMinMaxTree;
 
 pub type Block = u64;
 
-pub type BitString = bitwise::BitString<Block>;
+pub type Excess = i64;
+
+pub type BitString<S = Owned> = bitwise::BitString<Block, S>;
+
+const MAGIC: &[u8; 4] = b"PRNS";
 
 const OPEN: Bit = bitwise::ONE;
 const CLOSE: Bit = bitwise::ZERO;
@@ -55,6 +71,30 @@ impl Fixnum<Block> {
         }
         j.unwrap()
     }
+    fn relative_close_level(&self, child: Index, parent: Index) -> Index {
+        assert!(child <= parent, "{child} <= {parent}");
+        let opens = self.rank_one(parent) - self.rank_one(child);
+        (parent - child) - opens * 2
+    }
+    fn far_open_child(&self, index: Index, level: Index) -> Index {
+        if level == 0 {
+            return index;
+        }
+
+        let mut j = None;
+        let mut l = 0;
+        for i in index + 1..BLOCK_SIZE {
+            if self.get(i) == OPEN {
+                l += 1;
+                if level == l {
+                    j = Some(i);
+                }
+            } else {
+                l -= 1;
+            }
+        }
+        j.unwrap()
+    }
     fn left_excess(&self, index: Index) -> Index {
         let mut level: Index = 0;
         for i in 0..index {
@@ -69,9 +109,10 @@ impl Fixnum<Block> {
 }
 
 #[derive(Debug)]
-pub struct Parens<N> {
-    bits: BitString,
-    pioneers: Option<Box<PioneerFamily<N>>>,
+pub struct Parens<N, S: Storage = Owned> {
+    bits: BitString<S>,
+    pioneers: Option<Box<PioneerFamily<N, S>>>,
+    rmm: RangeMinMaxTree<S>,
 }
 impl<N> Parens<N>
 where
@@ -83,21 +124,29 @@ where
         } else {
             None
         };
-        Parens { bits, pioneers }
+        let rmm = RangeMinMaxTree::new(&bits);
+        Parens {
+            bits,
+            pioneers,
+            rmm,
+        }
     }
 }
-impl<N> ExternalByteSize for Parens<N>
+impl<N, S> ExternalByteSize for Parens<N, S>
 where
     N: ExternalByteSize,
+    S: Storage,
 {
     fn external_byte_size(&self) -> u64 {
         self.bits.external_byte_size()
             + self.pioneers.as_ref().map_or(0, |p| p.external_byte_size())
+            + self.rmm.external_byte_size()
     }
 }
-impl<N> Parens<N>
+impl<N, S> Parens<N, S>
 where
     N: NndOne,
+    S: Storage,
 {
     pub fn get_close(&self, index: Index) -> Option<Index> {
         debug_assert_eq!(self.bits.get(index).unwrap_or(OPEN), OPEN);
@@ -145,15 +194,225 @@ where
         });
         result
     }
+    pub fn get_open(&self, index: Index) -> Option<Index> {
+        debug_assert_eq!(self.bits.get(index).unwrap_or(CLOSE), CLOSE);
+        let base = index / BLOCK_SIZE;
+        let offset = index % BLOCK_SIZE;
+        let b = self.bits.as_fixnums().get(base as usize)?;
+        if let Some(i) = b.get_open(offset) {
+            return Some(base * BLOCK_SIZE + i);
+        }
+
+        let pioneers = self.pioneers.as_ref()?;
+        let close_pioneer = pioneers.succ(index);
+        debug_assert_eq!(close_pioneer / BLOCK_SIZE, base);
+        let level = b.relative_close_level(offset, close_pioneer % BLOCK_SIZE);
+
+        let open_pioneer = pioneers.get_open(close_pioneer);
+        let open_block = open_pioneer / BLOCK_SIZE;
+        let open_fix = &self.bits.as_fixnums()[open_block as usize];
+        let local_open_index = open_fix.far_open_child(open_pioneer % BLOCK_SIZE, level);
+        Some(open_block * BLOCK_SIZE + local_open_index)
+    }
     pub fn get(&self, index: Index) -> Option<Bit> {
         self.bits.get(index)
     }
 }
+impl<N, S: Storage> Parens<N, S> {
+    pub fn len(&self) -> Index {
+        self.bits.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// Returns the excess (the number of open minus close parentheses) of `0..=index`.
+    pub fn excess(&self, index: Index) -> Excess {
+        self.rmm.excess(&self.bits, index)
+    }
+
+    /// Returns the smallest `j > index` such that `excess(j) == excess(index) + delta`.
+    pub fn fwd_search(&self, index: Index, delta: Excess) -> Option<Index> {
+        self.rmm.fwd_search(&self.bits, index, delta)
+    }
+
+    /// Returns the largest `j <= index` such that `excess(j - 1) == excess(index) + delta`.
+    ///
+    /// `excess(-1)` is regarded as `0`.
+    pub fn bwd_search(&self, index: Index, delta: Excess) -> Option<Index> {
+        self.rmm.bwd_search(&self.bits, index, delta)
+    }
+
+    /// Returns the minimum excess in `start..=end`.
+    pub fn rmq(&self, start: Index, end: Index) -> Excess {
+        self.rmm.rmq(&self.bits, start, end)
+    }
+
+    /// Returns the leftmost index of the minimum excess in `start..=end`.
+    pub fn rmq_index(&self, start: Index, end: Index) -> Index {
+        self.rmm.rmq_index(&self.bits, start, end)
+    }
+
+    /// Returns the number of indices in `start..=end` at which the minimum excess is reached.
+    pub fn min_count(&self, start: Index, end: Index) -> u64 {
+        self.rmm.min_count(&self.bits, start, end)
+    }
+
+    /// Returns the `k`-th (from 1) index in `start..=end` at which the minimum excess is reached.
+    pub fn min_select(&self, start: Index, end: Index, k: u64) -> Option<Index> {
+        self.rmm.min_select(&self.bits, start, end, k)
+    }
+
+    pub fn find_close(&self, index: Index) -> Option<Index> {
+        debug_assert_eq!(self.bits.get(index), Some(OPEN));
+        self.fwd_search(index, -1)
+    }
+    pub fn find_open(&self, index: Index) -> Option<Index> {
+        debug_assert_eq!(self.bits.get(index), Some(CLOSE));
+        self.bwd_search(index, 0)
+    }
+    pub fn enclose(&self, index: Index) -> Option<Index> {
+        debug_assert_eq!(self.bits.get(index), Some(OPEN));
+        self.bwd_search(index, -2)
+    }
+}
+
+impl<N, S> WriteTo for Parens<N, S>
+where
+    N: WriteTo,
+    S: Storage,
+{
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, MAGIC)?;
+        self.bits.write_to(writer)?;
+        serialize::write_u64(writer, self.pioneers.is_some() as u64)?;
+        if let Some(pioneers) = &self.pioneers {
+            pioneers.nnd.write_to(writer)?;
+            pioneers.parens.write_to(writer)?;
+        }
+        self.rmm.write_to(writer)
+    }
+}
+impl<N> ReadFrom for Parens<N>
+where
+    N: ReadFrom,
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        Self::read_from_mut(&mut reader)
+    }
+}
+impl<N> Parens<N>
+where
+    N: ReadFrom,
+{
+    // Takes `&mut R` so that the recursive call for the pioneer family uses the same `R`.
+    fn read_from_mut<R: Read>(reader: &mut R) -> io::Result<Self> {
+        serialize::read_header(reader, MAGIC)?;
+        let bits = BitString::read_from(&mut *reader)?;
+        let pioneers = match serialize::read_u64(reader)? {
+            0 => None,
+            1 => {
+                let nnd = N::read_from(&mut *reader)?;
+                let parens = Parens::read_from_mut(reader)?;
+                Some(Box::new(PioneerFamily { nnd, parens }))
+            }
+            n => {
+                return Err(serialize::invalid_data(format!(
+                    "Unexpected pioneer flag: {n}"
+                )))
+            }
+        };
+        let rmm = RangeMinMaxTree::read_from(&mut *reader)?;
+        Parens::from_parts(bits, pioneers, rmm)
+    }
+}
+impl<'a, N> FromBytes<'a> for Parens<N, Borrowed<'a>>
+where
+    N: FromBytes<'a>,
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, MAGIC)?;
+        let bits = BitString::from_bytes(bytes)?;
+        let pioneers = match serialize::read_u64(bytes)? {
+            0 => None,
+            1 => {
+                let nnd = N::from_bytes(bytes)?;
+                let parens = Parens::from_bytes(bytes)?;
+                Some(Box::new(PioneerFamily { nnd, parens }))
+            }
+            n => {
+                return Err(serialize::invalid_data(format!(
+                    "Unexpected pioneer flag: {n}"
+                )))
+            }
+        };
+        let rmm = RangeMinMaxTree::from_bytes(bytes)?;
+        Parens::from_parts(bits, pioneers, rmm)
+    }
+}
+impl<N, S: Storage> Parens<N, S> {
+    fn from_parts(
+        bits: BitString<S>,
+        pioneers: Option<Box<PioneerFamily<N, S>>>,
+        rmm: RangeMinMaxTree<S>,
+    ) -> io::Result<Self> {
+        if rmm.leaves() != RangeMinMaxTree::required_leaves(&bits) {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected range min-max tree leaf count: bits={}, leaves={}",
+                bits.len(),
+                rmm.leaves()
+            )));
+        }
+        Ok(Parens {
+            bits,
+            pioneers,
+            rmm,
+        })
+    }
+}
+impl<N, S> GetClose for Parens<N, S>
+where
+    N: NndOne,
+    S: Storage,
+{
+    fn get_close(&self, index: Index) -> Option<Index> {
+        Parens::get_close(self, index)
+    }
+}
+impl<N, S> GetOpen for Parens<N, S>
+where
+    N: NndOne,
+    S: Storage,
+{
+    fn get_open(&self, index: Index) -> Option<Index> {
+        Parens::get_open(self, index)
+    }
+}
+impl<N, S: Storage> RankBit for Parens<N, S> {
+    fn rank_one(&self, index: Index) -> Rank {
+        ((self.excess(index) + index as Excess + 1) / 2) as Rank
+    }
+}
+impl<N, S: Storage> SelectOne for Parens<N, S> {
+    fn select_one(&self, rank: Rank) -> Option<Index> {
+        self.rmm.select_one(&self.bits, rank)
+    }
+}
+impl<N, S: Storage> SelectZero for Parens<N, S> {
+    fn select_zero(&self, rank: Rank) -> Option<Index> {
+        self.rmm.select_zero(&self.bits, rank)
+    }
+}
+impl<N, S: Storage> SuccZero for Parens<N, S> {
+    fn succ_zero(&self, index: Index) -> Option<Index> {
+        self.bits.succ_zero(index)
+    }
+}
 
 #[derive(Debug)]
-struct PioneerFamily<N> {
+struct PioneerFamily<N, S: Storage = Owned> {
     nnd: N,
-    parens: Parens<N>,
+    parens: Parens<N, S>,
 }
 impl<N> PioneerFamily<N>
 where
@@ -167,27 +426,37 @@ where
         }
     }
 }
-impl<N> ExternalByteSize for PioneerFamily<N>
+impl<N, S> ExternalByteSize for PioneerFamily<N, S>
 where
     N: ExternalByteSize,
+    S: Storage,
 {
     fn external_byte_size(&self) -> u64 {
         self.nnd.external_byte_size() + self.parens.external_byte_size()
     }
 }
-impl<N> PioneerFamily<N>
+impl<N, S> PioneerFamily<N, S>
 where
     N: NndOne,
+    S: Storage,
 {
     fn pred(&self, index: Index) -> Index {
         self.nnd.pred_one(index).unwrap()
     }
+    fn succ(&self, index: Index) -> Index {
+        self.nnd.succ_one(index).unwrap()
+    }
     fn get_close(&self, index: Index) -> Index {
         // NOTE: predとまとめればrank呼び出し回数を減らせる
         let rank = self.nnd.rank_one(index);
         let close = self.parens.get_close(rank - 1).unwrap();
         self.nnd.select_one(close + 1).unwrap()
     }
+    fn get_open(&self, index: Index) -> Index {
+        let rank = self.nnd.rank_one(index);
+        let open = self.parens.get_open(rank - 1).unwrap();
+        self.nnd.select_one(open + 1).unwrap()
+    }
 }
 
 // TODO: optimize
@@ -237,3 +506,159 @@ fn extract_pioneers(bits: &BitString) -> (BitString, BitString) {
         .collect::<BitString>();
     (flags, parens)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitwise::SparseOneNnd;
+
+    fn random_parens(size: usize) -> BitString {
+        let mut x = 0x2545_F491_4F6C_DD1Du64;
+        let mut bits = BitString::new();
+        let mut depth = 0;
+        bits.push(OPEN);
+        for i in 0..size {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            let open = if (1000..2000).contains(&i) {
+                true
+            } else if (2000..3000).contains(&i) {
+                false
+            } else {
+                x % 5 < 2
+            };
+            if open {
+                depth += 1;
+                bits.push(OPEN);
+            } else if depth > 0 {
+                depth -= 1;
+                bits.push(CLOSE);
+            }
+        }
+        for _ in 0..depth {
+            bits.push(CLOSE);
+        }
+        bits.push(CLOSE);
+        bits
+    }
+
+    fn excesses(bits: &BitString) -> Vec<Excess> {
+        bits.iter()
+            .scan(0, |e, b| {
+                *e += if b == OPEN { 1 } else { -1 };
+                Some(*e)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_close_and_open() {
+        for size in [10, 100, 700, 5000] {
+            check_find_close_and_open(random_parens(size));
+        }
+    }
+
+    fn check_find_close_and_open(bits: BitString) {
+        let excesses = excesses(&bits);
+        let parens = Parens::<SparseOneNnd>::new(bits.clone());
+
+        let mut stack = Vec::new();
+        let mut opens = 0;
+        for (i, b) in bits.iter().enumerate() {
+            let i = i as Index;
+            assert_eq!(parens.excess(i), excesses[i as usize]);
+            assert_eq!(
+                parens.succ_zero(i),
+                bits.iter()
+                    .skip(i as usize)
+                    .position(|b| b == CLOSE)
+                    .map(|j| i + j as Index)
+            );
+            if b == OPEN {
+                opens += 1;
+                assert_eq!(parens.select_one(opens), Some(i));
+                assert_eq!(parens.rank_one(i), opens);
+                assert_eq!(parens.enclose(i), stack.last().cloned());
+                stack.push(i);
+            } else {
+                assert_eq!(parens.select_zero(i + 1 - opens), Some(i));
+                let open = stack.pop().unwrap();
+                assert_eq!(parens.find_close(open), Some(i));
+                assert_eq!(parens.get_close(open), Some(i));
+                assert_eq!(parens.find_open(i), Some(open));
+                assert_eq!(parens.get_open(i), Some(open));
+            }
+        }
+        assert_eq!(parens.select_one(0), None);
+        assert_eq!(parens.select_one(opens + 1), None);
+        assert_eq!(parens.select_zero(0), None);
+        assert_eq!(parens.select_zero(bits.len() - opens + 1), None);
+    }
+
+    #[test]
+    fn fwd_and_bwd_search() {
+        let bits = random_parens(3000);
+        let excesses = excesses(&bits);
+        let parens = Parens::<SparseOneNnd>::new(bits);
+
+        for i in (0..excesses.len()).step_by(7) {
+            for delta in [-3, -1, 0, 1, 2, 40] {
+                let target = excesses[i] + delta;
+                let fwd = (i + 1..excesses.len())
+                    .find(|&j| excesses[j] == target)
+                    .map(|j| j as Index);
+                assert_eq!(parens.fwd_search(i as Index, delta), fwd);
+
+                let bwd = (0..=i)
+                    .rev()
+                    .find(|&j| j.checked_sub(1).map_or(0, |k| excesses[k]) == target)
+                    .map(|j| j as Index);
+                assert_eq!(parens.bwd_search(i as Index, delta), bwd);
+            }
+        }
+    }
+
+    #[test]
+    fn rmq() {
+        let bits = random_parens(3000);
+        let excesses = excesses(&bits);
+        let parens = Parens::<SparseOneNnd>::new(bits);
+
+        for i in (0..excesses.len()).step_by(13) {
+            for j in (i..excesses.len()).step_by(101) {
+                let min = *excesses[i..=j].iter().min().unwrap();
+                let index = i + excesses[i..=j].iter().position(|&e| e == min).unwrap();
+                assert_eq!(parens.rmq(i as Index, j as Index), min);
+                assert_eq!(parens.rmq_index(i as Index, j as Index), index as Index);
+            }
+        }
+    }
+
+    #[test]
+    fn min_count_and_select() {
+        let bits = random_parens(20000);
+        let excesses = excesses(&bits);
+        let parens = Parens::<SparseOneNnd>::new(bits);
+
+        for i in (0..excesses.len()).step_by(997) {
+            for j in (i..excesses.len()).step_by(1511) {
+                let min = *excesses[i..=j].iter().min().unwrap();
+                let indices = (i..=j)
+                    .filter(|&k| excesses[k] == min)
+                    .map(|k| k as Index)
+                    .collect::<Vec<_>>();
+                let (i, j) = (i as Index, j as Index);
+                assert_eq!(parens.min_count(i, j), indices.len() as u64);
+                for k in [1, 2, indices.len() / 2 + 1, indices.len()] {
+                    assert_eq!(
+                        parens.min_select(i, j, k as u64),
+                        indices.get(k - 1).cloned()
+                    );
+                }
+                assert_eq!(parens.min_select(i, j, 0), None);
+                assert_eq!(parens.min_select(i, j, indices.len() as u64 + 1), None);
+            }
+        }
+    }
+}
@@ -0,0 +1,659 @@
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
+use std::mem;
+
+use super::parentheses::BitString;
+use super::parentheses::Excess;
+use crate::bitwise::ops::ExternalByteSize;
+use crate::bitwise::ops::RankBit;
+use crate::bitwise::ops::SelectOne;
+use crate::bitwise::ops::SelectZero;
+use crate::bitwise::Bit;
+use crate::bitwise::Index;
+use crate::bitwise::Rank;
+use crate::serialize::{self, FromBytes, Pod, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
+
+const MAGIC: &[u8; 4] = b"RMMT";
+
+const FIXNUM_SIZE: Index = 64;
+const LEAF_SIZE: Index = FIXNUM_SIZE * 16;
+
+const BYTE_EXCESSES: [ByteExcess; 256] = byte_excesses();
+
+#[derive(Debug, Clone, Copy)]
+struct ByteExcess {
+    total: i8,
+    min: i8,
+    max: i8,
+    min_count: u8,
+}
+impl ByteExcess {
+    fn contains(&self, base: Excess, excess: Excess) -> bool {
+        base + self.min as Excess <= excess && excess <= base + self.max as Excess
+    }
+}
+
+const fn byte_excesses() -> [ByteExcess; 256] {
+    let mut table = [ByteExcess {
+        total: 0,
+        min: 0,
+        max: 0,
+        min_count: 0,
+    }; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut excess = 0;
+        let mut min = i8::MAX;
+        let mut max = i8::MIN;
+        let mut min_count = 0;
+        let mut i = 0;
+        while i < 8 {
+            if (byte >> i) & 1 == 1 {
+                excess += 1;
+            } else {
+                excess -= 1;
+            }
+            if excess < min {
+                min = excess;
+                min_count = 0;
+            }
+            if excess == min {
+                min_count += 1;
+            }
+            if excess > max {
+                max = excess;
+            }
+            i += 1;
+        }
+        table[byte] = ByteExcess {
+            total: excess,
+            min,
+            max,
+            min_count,
+        };
+        byte += 1;
+    }
+    table
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct MinMax {
+    min: u32,
+    max: u32,
+    min_count: u32,
+}
+unsafe impl Pod for MinMax {
+    fn to_le(self) -> Self {
+        MinMax {
+            min: self.min.to_le(),
+            max: self.max.to_le(),
+            min_count: self.min_count.to_le(),
+        }
+    }
+}
+impl MinMax {
+    fn contains(&self, excess: Excess) -> bool {
+        self.min as Excess <= excess && excess <= self.max as Excess
+    }
+    fn count(&self, excess: Excess) -> u64 {
+        if self.min as Excess == excess {
+            self.min_count as u64
+        } else {
+            0
+        }
+    }
+    fn merge(&self, other: &Self) -> Self {
+        let min_count = match self.min.cmp(&other.min) {
+            Ordering::Less => self.min_count,
+            Ordering::Equal => self.min_count + other.min_count,
+            Ordering::Greater => other.min_count,
+        };
+        MinMax {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            min_count,
+        }
+    }
+}
+
+// Range min-max tree over the excess sequence `E(i) = #open - #close in bits[0..=i]`.
+//
+// Each leaf covers `LEAF_SIZE` bits and keeps the minimum and maximum of `E` in its range,
+// and the number of positions at which the minimum is reached.
+// The upper levels are stored bottom-up in `nodes` (`levels[l]..levels[l + 1]` is level `l`),
+// and the node `i` of level `l + 1` covers the nodes `2 * i` and `2 * i + 1` of level `l`.
+#[derive(Debug)]
+pub struct RangeMinMaxTree<S: Storage = Owned> {
+    leaf_excesses: S::Slice<u32>,
+    nodes: S::Slice<MinMax>,
+    levels: Vec<usize>,
+}
+impl RangeMinMaxTree {
+    pub fn new(bits: &BitString) -> Self {
+        let leaves = Self::required_leaves(bits);
+        let mut leaf_excesses = Vec::with_capacity(leaves);
+        let mut nodes = Vec::with_capacity(leaves * 2);
+
+        let mut excess = 0;
+        for leaf in 0..leaves {
+            leaf_excesses.push(to_u32(excess));
+            let start = leaf as Index * LEAF_SIZE;
+            let end = (start + LEAF_SIZE).min(bits.len());
+            let (min, max, last) = scan_min_max(bits, start, end, excess);
+            let min_count = scan_count(bits, start, end, excess, min, Rank::MAX).unwrap_err();
+            nodes.push(MinMax {
+                min: to_u32(min),
+                max: to_u32(max),
+                min_count: min_count as u32,
+            });
+            excess = last;
+        }
+
+        let levels = level_offsets(leaves);
+        for level in levels.windows(2).take(levels.len() - 2) {
+            let (start, end) = (level[0], level[1]);
+            for i in (start..end).step_by(2) {
+                let node = if i + 1 < end {
+                    nodes[i].merge(&nodes[i + 1])
+                } else {
+                    nodes[i]
+                };
+                nodes.push(node);
+            }
+        }
+        debug_assert_eq!(nodes.len(), levels[levels.len() - 1]);
+
+        RangeMinMaxTree {
+            leaf_excesses,
+            nodes,
+            levels,
+        }
+    }
+}
+impl<S: Storage> RangeMinMaxTree<S> {
+    pub fn excess(&self, bits: &BitString<S>, index: Index) -> Excess {
+        let leaf = (index / LEAF_SIZE) as usize;
+        let fixnums = bits.as_fixnums();
+        let first = (leaf as Index * LEAF_SIZE / FIXNUM_SIZE) as usize;
+        let last = (index / FIXNUM_SIZE) as usize;
+        let offset = index % FIXNUM_SIZE;
+
+        let mut excess = self.leaf_excesses[leaf] as Excess;
+        for b in &fixnums[first..last] {
+            excess += 2 * b.pop_count() as Excess - FIXNUM_SIZE as Excess;
+        }
+        excess + 2 * fixnums[last].rank_one(offset) as Excess - (offset + 1) as Excess
+    }
+
+    pub fn fwd_search(&self, bits: &BitString<S>, index: Index, delta: Excess) -> Option<Index> {
+        let excess = self.excess(bits, index);
+        let target = excess + delta;
+        let leaf = (index / LEAF_SIZE) as usize;
+        if let Ok(i) = scan_fwd(bits, index + 1, self.leaf_end(bits, leaf), excess, target) {
+            return Some(i);
+        }
+
+        let (mut level, mut i) = (0, leaf);
+        loop {
+            if i % 2 == 0 && self.node(level, i + 1).is_some_and(|n| n.contains(target)) {
+                i += 1;
+                break;
+            }
+            level += 1;
+            i /= 2;
+            if level + 1 >= self.levels.len() {
+                return None;
+            }
+        }
+        while level > 0 {
+            level -= 1;
+            i *= 2;
+            if !self.node(level, i).is_some_and(|n| n.contains(target)) {
+                i += 1;
+            }
+        }
+
+        let start = i as Index * LEAF_SIZE;
+        let excess = self.leaf_excesses[i] as Excess;
+        scan_fwd(bits, start, self.leaf_end(bits, i), excess, target).ok()
+    }
+
+    pub fn bwd_search(&self, bits: &BitString<S>, index: Index, delta: Excess) -> Option<Index> {
+        let excess = self.excess(bits, index);
+        let target = excess + delta;
+        let leaf = (index / LEAF_SIZE) as usize;
+        let start = leaf as Index * LEAF_SIZE;
+        let prev_excess = excess - bit_excess(bits, index);
+        if let Ok(i) = scan_bwd(bits, start, index, prev_excess, target) {
+            return Some(i + 1);
+        }
+
+        let (mut level, mut i) = (0, leaf);
+        loop {
+            if i % 2 == 1 && self.node(level, i - 1).is_some_and(|n| n.contains(target)) {
+                i -= 1;
+                break;
+            }
+            level += 1;
+            i /= 2;
+            if level + 1 >= self.levels.len() {
+                return if target == 0 { Some(0) } else { None };
+            }
+        }
+        while level > 0 {
+            level -= 1;
+            i = i * 2 + 1;
+            if !self.node(level, i).is_some_and(|n| n.contains(target)) {
+                i -= 1;
+            }
+        }
+
+        let start = i as Index * LEAF_SIZE;
+        let end = self.leaf_end(bits, i);
+        let excess = self.excess(bits, end - 1);
+        scan_bwd(bits, start, end, excess, target)
+            .ok()
+            .map(|i| i + 1)
+    }
+
+    pub fn rmq(&self, bits: &BitString<S>, start: Index, end: Index) -> Excess {
+        assert!(start <= end, "{start} <= {end}");
+        let start_leaf = (start / LEAF_SIZE) as usize;
+        let end_leaf = (end / LEAF_SIZE) as usize;
+        let prev_excess = self.excess(bits, start) - bit_excess(bits, start);
+        if start_leaf == end_leaf {
+            return scan_min_max(bits, start, end + 1, prev_excess).0;
+        }
+
+        let leaf_end = self.leaf_end(bits, start_leaf);
+        let mut min = scan_min_max(bits, start, leaf_end, prev_excess).0;
+
+        let (mut level, mut l, mut r) = (0, start_leaf + 1, end_leaf);
+        while l < r {
+            if l % 2 == 1 {
+                min = min.min(self.nodes[self.levels[level] + l].min as Excess);
+                l += 1;
+            }
+            if r % 2 == 1 {
+                r -= 1;
+                min = min.min(self.nodes[self.levels[level] + r].min as Excess);
+            }
+            level += 1;
+            l /= 2;
+            r /= 2;
+        }
+
+        let leaf_start = end_leaf as Index * LEAF_SIZE;
+        let excess = self.leaf_excesses[end_leaf] as Excess;
+        min.min(scan_min_max(bits, leaf_start, end + 1, excess).0)
+    }
+
+    pub fn rmq_index(&self, bits: &BitString<S>, start: Index, end: Index) -> Index {
+        let min = self.rmq(bits, start, end);
+        let excess = self.excess(bits, start);
+        if excess == min {
+            start
+        } else {
+            self.fwd_search(bits, start, min - excess).unwrap()
+        }
+    }
+
+    /// Returns the number of positions in `start..=end` at which the minimum excess is reached.
+    pub fn min_count(&self, bits: &BitString<S>, start: Index, end: Index) -> u64 {
+        let min = self.rmq(bits, start, end);
+        self.select_excess(bits, start, end, min, Rank::MAX)
+            .unwrap_err()
+    }
+
+    /// Returns the `k`-th (from 1) position in `start..=end` at which the minimum excess is reached.
+    pub fn min_select(
+        &self,
+        bits: &BitString<S>,
+        start: Index,
+        end: Index,
+        k: u64,
+    ) -> Option<Index> {
+        if k == 0 {
+            return None;
+        }
+        let min = self.rmq(bits, start, end);
+        self.select_excess(bits, start, end, min, k).ok()
+    }
+
+    // Returns the `k`-th position in `start..=end` such that `E(i) == target`,
+    // or the number of such positions if there are fewer than `k`.
+    //
+    // `target` must be the minimum of `E` in the range.
+    fn select_excess(
+        &self,
+        bits: &BitString<S>,
+        start: Index,
+        end: Index,
+        target: Excess,
+        k: u64,
+    ) -> Result<Index, u64> {
+        let start_leaf = (start / LEAF_SIZE) as usize;
+        let end_leaf = (end / LEAF_SIZE) as usize;
+        let prev_excess = self.excess(bits, start) - bit_excess(bits, start);
+        if start_leaf == end_leaf {
+            return scan_count(bits, start, end + 1, prev_excess, target, k);
+        }
+
+        let leaf_end = self.leaf_end(bits, start_leaf);
+        let mut rest = match scan_count(bits, start, leaf_end, prev_excess, target, k) {
+            Ok(i) => return Ok(i),
+            Err(count) => k - count,
+        };
+
+        let (mut lefts, mut rights) = (Vec::new(), Vec::new());
+        let (mut level, mut l, mut r) = (0, start_leaf + 1, end_leaf);
+        while l < r {
+            if l % 2 == 1 {
+                lefts.push((level, l));
+                l += 1;
+            }
+            if r % 2 == 1 {
+                r -= 1;
+                rights.push((level, r));
+            }
+            level += 1;
+            l /= 2;
+            r /= 2;
+        }
+        for (mut level, mut i) in lefts.into_iter().chain(rights.into_iter().rev()) {
+            let count = self.nodes[self.levels[level] + i].count(target);
+            if count < rest {
+                rest -= count;
+                continue;
+            }
+            while level > 0 {
+                level -= 1;
+                i *= 2;
+                let count = self.nodes[self.levels[level] + i].count(target);
+                if count < rest {
+                    rest -= count;
+                    i += 1;
+                }
+            }
+            let (start, end) = (i as Index * LEAF_SIZE, self.leaf_end(bits, i));
+            let excess = self.leaf_excesses[i] as Excess;
+            return Ok(scan_count(bits, start, end, excess, target, rest).unwrap());
+        }
+
+        let leaf_start = end_leaf as Index * LEAF_SIZE;
+        let excess = self.leaf_excesses[end_leaf] as Excess;
+        scan_count(bits, leaf_start, end + 1, excess, target, rest)
+            .map_err(|count| k - rest + count)
+    }
+
+    pub fn select_one(&self, bits: &BitString<S>, rank: Rank) -> Option<Index> {
+        self.select(bits, rank, true)
+    }
+    pub fn select_zero(&self, bits: &BitString<S>, rank: Rank) -> Option<Index> {
+        self.select(bits, rank, false)
+    }
+
+    // The number of open parentheses before a leaf is derived from its `E(start - 1)`,
+    // so the leaf is found by binary search and the rest is scanned fixnum by fixnum.
+    fn select(&self, bits: &BitString<S>, rank: Rank, bit: Bit) -> Option<Index> {
+        if rank == 0 || self.leaves() == 0 {
+            return None;
+        }
+        let before = |leaf: usize| {
+            let start = leaf as Index * LEAF_SIZE;
+            let opens = (self.leaf_excesses[leaf] as Index + start) / 2;
+            if bit {
+                opens
+            } else {
+                start - opens
+            }
+        };
+        let (mut leaf, mut end) = (0, self.leaves());
+        while end - leaf > 1 {
+            let mid = leaf + (end - leaf) / 2;
+            if before(mid) < rank {
+                leaf = mid;
+            } else {
+                end = mid;
+            }
+        }
+
+        let mut rest = rank - before(leaf);
+        let first = (leaf as Index * LEAF_SIZE / FIXNUM_SIZE) as usize;
+        for (i, b) in bits.as_fixnums().iter().enumerate().skip(first) {
+            let ones = b.pop_count() as Rank;
+            let count = if bit { ones } else { FIXNUM_SIZE - ones };
+            if count < rest {
+                rest -= count;
+            } else {
+                let offset = if bit {
+                    b.select_one(rest)
+                } else {
+                    b.select_zero(rest)
+                };
+                let index = i as Index * FIXNUM_SIZE + offset.unwrap();
+                return Some(index).filter(|&i| i < bits.len());
+            }
+        }
+        None
+    }
+
+    pub fn leaves(&self) -> usize {
+        self.leaf_excesses.len()
+    }
+    pub fn required_leaves(bits: &BitString<S>) -> usize {
+        bits.len().div_ceil(LEAF_SIZE) as usize
+    }
+
+    fn node(&self, level: usize, i: usize) -> Option<&MinMax> {
+        let start = self.levels[level];
+        let end = self.levels[level + 1];
+        self.nodes[start..end].get(i)
+    }
+    fn leaf_end(&self, bits: &BitString<S>, leaf: usize) -> Index {
+        ((leaf + 1) as Index * LEAF_SIZE).min(bits.len())
+    }
+}
+impl<S: Storage> ExternalByteSize for RangeMinMaxTree<S> {
+    fn external_byte_size(&self) -> u64 {
+        self.leaf_excesses.len() as u64 * mem::size_of::<u32>() as u64
+            + self.nodes.len() as u64 * mem::size_of::<MinMax>() as u64
+            + self.levels.len() as u64 * mem::size_of::<usize>() as u64
+    }
+}
+
+impl<S: Storage> WriteTo for RangeMinMaxTree<S> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, MAGIC)?;
+        serialize::write_slice(writer, &self.leaf_excesses)?;
+        serialize::write_slice(writer, &self.nodes)
+    }
+}
+impl ReadFrom for RangeMinMaxTree {
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, MAGIC)?;
+        let leaf_excesses = serialize::read_vec(&mut reader)?;
+        let nodes = serialize::read_vec(&mut reader)?;
+        Self::from_arrays(leaf_excesses, nodes)
+    }
+}
+impl<'a> FromBytes<'a> for RangeMinMaxTree<Borrowed<'a>> {
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, MAGIC)?;
+        let leaf_excesses = serialize::view_slice(bytes)?;
+        let nodes = serialize::view_slice(bytes)?;
+        Self::from_arrays(leaf_excesses, nodes)
+    }
+}
+impl<S: Storage> RangeMinMaxTree<S> {
+    fn from_arrays(leaf_excesses: S::Slice<u32>, nodes: S::Slice<MinMax>) -> io::Result<Self> {
+        let levels = level_offsets(leaf_excesses.len());
+        if nodes.len() != levels[levels.len() - 1] {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected range min-max tree node count: leaves={}, nodes={}",
+                leaf_excesses.len(),
+                nodes.len()
+            )));
+        }
+        Ok(RangeMinMaxTree {
+            leaf_excesses,
+            nodes,
+            levels,
+        })
+    }
+}
+
+// Returns the start offsets of the levels (and the total node count) for the given leaf count.
+fn level_offsets(leaves: usize) -> Vec<usize> {
+    let mut levels = vec![0, leaves];
+    let mut size = leaves;
+    while size > 1 {
+        size = size.div_ceil(2);
+        levels.push(levels[levels.len() - 1] + size);
+    }
+    levels
+}
+
+fn to_u32(excess: Excess) -> u32 {
+    u32::try_from(excess).unwrap_or_else(|_| panic!("Excess out of range: {excess}"))
+}
+
+fn bit_excess<S: Storage>(bits: &BitString<S>, index: Index) -> Excess {
+    if bits.get(index).unwrap() {
+        1
+    } else {
+        -1
+    }
+}
+
+fn byte_at<S: Storage>(bits: &BitString<S>, index: Index) -> &'static ByteExcess {
+    debug_assert_eq!(index % 8, 0);
+    let b = bits.as_fixnums()[(index / FIXNUM_SIZE) as usize].to_inner();
+    &BYTE_EXCESSES[((b >> (index % FIXNUM_SIZE)) & 0xFF) as usize]
+}
+
+// Returns the minimum and maximum of `E(i)` for `i` in `start..end`, and `E(end - 1)`.
+fn scan_min_max<S: Storage>(
+    bits: &BitString<S>,
+    start: Index,
+    end: Index,
+    mut excess: Excess,
+) -> (Excess, Excess, Excess) {
+    let (mut min, mut max) = (Excess::MAX, Excess::MIN);
+    let mut i = start;
+    while i < end {
+        if i.is_multiple_of(8) && i + 8 <= end {
+            let b = byte_at(bits, i);
+            min = min.min(excess + b.min as Excess);
+            max = max.max(excess + b.max as Excess);
+            excess += b.total as Excess;
+            i += 8;
+        } else {
+            excess += bit_excess(bits, i);
+            min = min.min(excess);
+            max = max.max(excess);
+            i += 1;
+        }
+    }
+    (min, max, excess)
+}
+
+// Returns the `k`-th `i` in `start..end` such that `E(i) == target`,
+// or the number of such indices if there are fewer than `k`.
+//
+// `excess` is `E(start - 1)`.
+fn scan_count<S: Storage>(
+    bits: &BitString<S>,
+    start: Index,
+    end: Index,
+    mut excess: Excess,
+    target: Excess,
+    k: u64,
+) -> Result<Index, u64> {
+    let mut count = 0;
+    let mut i = start;
+    while i < end {
+        if i.is_multiple_of(8) && i + 8 <= end {
+            let b = byte_at(bits, i);
+            let min = excess + b.min as Excess;
+            if min > target || (min == target && count + (b.min_count as u64) < k) {
+                if min == target {
+                    count += b.min_count as u64;
+                }
+                excess += b.total as Excess;
+                i += 8;
+                continue;
+            }
+        }
+        excess += bit_excess(bits, i);
+        if excess == target {
+            count += 1;
+            if count == k {
+                return Ok(i);
+            }
+        }
+        i += 1;
+    }
+    Err(count)
+}
+
+// Returns the first `i` in `start..end` such that `E(i) == target`.
+//
+// `excess` is `E(start - 1)`, and `E(end - 1)` is returned if there is no such index.
+fn scan_fwd<S: Storage>(
+    bits: &BitString<S>,
+    start: Index,
+    end: Index,
+    mut excess: Excess,
+    target: Excess,
+) -> Result<Index, Excess> {
+    let mut i = start;
+    while i < end {
+        if i.is_multiple_of(8) && i + 8 <= end {
+            let b = byte_at(bits, i);
+            if !b.contains(excess, target) {
+                excess += b.total as Excess;
+                i += 8;
+                continue;
+            }
+        }
+        excess += bit_excess(bits, i);
+        if excess == target {
+            return Ok(i);
+        }
+        i += 1;
+    }
+    Err(excess)
+}
+
+// Returns the last `i` in `start..end` such that `E(i) == target`.
+//
+// `excess` is `E(end - 1)`, and `E(start - 1)` is returned if there is no such index.
+fn scan_bwd<S: Storage>(
+    bits: &BitString<S>,
+    start: Index,
+    end: Index,
+    mut excess: Excess,
+    target: Excess,
+) -> Result<Index, Excess> {
+    let mut i = end;
+    while i > start {
+        if i.is_multiple_of(8) && i - 8 >= start {
+            let b = byte_at(bits, i - 8);
+            let base = excess - b.total as Excess;
+            if !b.contains(base, target) {
+                excess = base;
+                i -= 8;
+                continue;
+            }
+        }
+        i -= 1;
+        if excess == target {
+            return Ok(i);
+        }
+        excess -= bit_excess(bits, i);
+    }
+    Err(excess)
+}
@@ -0,0 +1,427 @@
+//! Depth-First Unary Degree Sequence (DFUDS) representation.
+//!
+//! Each node is described by `(^degree )` in preorder, and an extra `(` is prepended so that
+//! the whole sequence is balanced. A node is identified by the start position of its description,
+//! and its id is its preorder number (the root is `0`) as in `BalancedParensTree`.
+//!
+//! The `k`-th child of a node starting at `x` starts right after the close parenthesis matching
+//! the open parenthesis at `x + degree - 1 - k`.
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use super::balanced_parens::parentheses::Parens;
+use super::Edge;
+use super::LabelVec;
+use super::Labels;
+use super::LabelsMut;
+use super::NodeId;
+use crate::bitwise::ops::ExternalByteSize;
+use crate::bitwise::ops::NndOne;
+use crate::bitwise::ops::RankBit;
+use crate::bitwise::ops::SelectZero;
+use crate::bitwise::ops::SuccZero;
+use crate::bitwise::BitString;
+use crate::bitwise::Index;
+use crate::bitwise::Rank;
+use crate::bitwise::SparseOneNnd;
+use crate::tree::traversal::DepthFirstIter;
+use crate::tree::traversal::DepthFirstTraverse;
+
+pub struct DfudsTree<L, N = SparseOneNnd> {
+    labels: L,
+    parens: Parens<N>,
+}
+impl<L> DfudsTree<LabelVec<L>, SparseOneNnd>
+where
+    L: Clone + Debug,
+{
+    pub fn new<T>(tree: T) -> Self
+    where
+        T: DepthFirstTraverse<Label = L>,
+    {
+        Self::new_builder(tree, LabelVec::new()).build_all()
+    }
+}
+impl<L, N> DfudsTree<L, N>
+where
+    L: LabelsMut,
+    N: NndOne + From<BitString>,
+{
+    pub fn new_builder<T>(tree: T, labels: L) -> Builder<T, L, N>
+    where
+        T: DepthFirstTraverse<Label = L::Label>,
+    {
+        Builder::new(tree, labels)
+    }
+}
+impl<L, N> DfudsTree<L, N>
+where
+    L: ExternalByteSize,
+    N: ExternalByteSize,
+{
+    pub fn external_byte_size(&self) -> u64 {
+        self.labels.external_byte_size() + self.parens.external_byte_size()
+    }
+}
+impl<L, N> DfudsTree<L, N>
+where
+    L: Labels,
+    N: NndOne,
+{
+    pub fn root(&self) -> Node<L, N, &Self> {
+        Node::new(1, 0, self)
+    }
+    pub fn to_owned_root(self) -> Node<L, N, Rc<Self>> {
+        Node::new(1, 0, Rc::new(self))
+    }
+    pub fn node(&self, id: NodeId) -> Option<Node<L, N, &Self>> {
+        let start = self.start(id)?;
+        Some(Node::new(start as NodeId, id, self))
+    }
+
+    // The description of the node starts after the `id`-th close parenthesis.
+    fn start(&self, id: NodeId) -> Option<Index> {
+        if id == 0 {
+            Some(1)
+        } else {
+            let start = self.parens.select_zero(id as Rank)? + 1;
+            Some(start).filter(|&i| i < self.parens.len())
+        }
+    }
+}
+impl<L, N> DfudsTree<L, N>
+where
+    L: Labels,
+{
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.labels.len() == 0
+    }
+}
+impl<L, N> DfudsTree<L, N> {
+    pub fn labels(&self) -> &L {
+        &self.labels
+    }
+    pub fn parens(&self) -> &Parens<N> {
+        &self.parens
+    }
+}
+
+pub struct Builder<T, L, N = SparseOneNnd> {
+    iter: DepthFirstIter<T>,
+    labels: L,
+    degrees: Vec<u32>,
+    path: Vec<usize>,
+    _nnd: PhantomData<N>,
+}
+impl<T, L, N> Builder<T, L, N>
+where
+    T: DepthFirstTraverse,
+    L: LabelsMut<Label = T::Label>,
+    N: NndOne + From<BitString>,
+{
+    pub fn new(tree: T, labels: L) -> Self {
+        Builder {
+            iter: DepthFirstIter::new(tree),
+            labels,
+            degrees: vec![0], // The root
+            path: vec![0],
+            _nnd: PhantomData,
+        }
+    }
+    pub fn build_once(&mut self) -> bool {
+        if let Some(node) = self.iter.next() {
+            self.path.truncate(node.level + 1);
+            self.degrees[*self.path.last().unwrap()] += 1;
+            self.path.push(self.degrees.len());
+            self.degrees.push(0);
+            self.labels.push(node.label);
+            true
+        } else {
+            false
+        }
+    }
+    pub fn finish(mut self) -> DfudsTree<L, N> {
+        let mut parens = BitString::new();
+        parens.push(true); // The extra open parenthesis
+        for degree in self.degrees {
+            for _ in 0..degree {
+                parens.push(true);
+            }
+            parens.push(false);
+        }
+        self.labels.shrink_to_fit();
+        DfudsTree {
+            labels: self.labels,
+            parens: Parens::new(parens),
+        }
+    }
+    pub fn build_all(mut self) -> DfudsTree<L, N> {
+        while self.build_once() {}
+        self.finish()
+    }
+}
+
+pub struct Node<L, N, T> {
+    id: NodeId,
+    inner_id: NodeId,
+    tree: T,
+    _n: PhantomData<N>,
+    _l: PhantomData<L>,
+}
+impl<L, N, T> Node<L, N, T>
+where
+    L: Labels,
+    N: NndOne,
+    T: ::std::ops::Deref<Target = DfudsTree<L, N>> + Clone,
+{
+    fn new(inner_id: NodeId, id: NodeId, tree: T) -> Self {
+        Node {
+            id,
+            inner_id,
+            tree,
+            _n: PhantomData,
+            _l: PhantomData,
+        }
+    }
+    pub fn is_root(&self) -> bool {
+        self.id == 0
+    }
+    pub fn label(&self) -> Option<L::Label> {
+        self.id
+            .checked_sub(1)
+            .and_then(|i| self.tree.labels.get(i as usize))
+    }
+    pub fn is_leaf(&self) -> bool {
+        !self.tree.parens.get(self.inner_id as Index).unwrap()
+    }
+    pub fn degree(&self) -> usize {
+        let start = self.inner_id as Index;
+        (self.tree.parens.succ_zero(start).unwrap() - start) as usize
+    }
+
+    /// Returns the `k`-th (from 0) child.
+    pub fn child(&self, k: usize) -> Option<Self> {
+        let degree = self.degree();
+        if k >= degree {
+            return None;
+        }
+        Some(self.child_at(self.child_start(degree, k)))
+    }
+
+    // Returns the start position of the `k`-th child.
+    fn child_start(&self, degree: usize, k: usize) -> Index {
+        let start = self.inner_id as Index;
+        if k == 0 {
+            start + degree as Index + 1
+        } else {
+            let open = start + (degree - 1 - k) as Index;
+            self.tree.parens.get_close(open).unwrap() + 1
+        }
+    }
+    fn child_at(&self, start: Index) -> Self {
+        let id = self.tree.parens.rank_zero(start - 1) as NodeId;
+        Self::new(start as NodeId, id, self.tree.clone())
+    }
+    pub fn parent(&self) -> Option<Self> {
+        let (open, _) = self.parent_open()?;
+        Some(self.node_at(open))
+    }
+
+    /// Returns the number of preceding siblings (`0` for the root).
+    pub fn child_rank(&self) -> usize {
+        self.parent_open().map_or(0, |(open, parent_start)| {
+            let last = self.tree.parens.succ_zero(parent_start).unwrap() - 1;
+            (last - open) as usize
+        })
+    }
+
+    // Returns the open parenthesis of the parent that corresponds to this node,
+    // and the start position of the parent.
+    fn parent_open(&self) -> Option<(Index, Index)> {
+        if self.is_root() {
+            return None;
+        }
+        let open = self
+            .tree
+            .parens
+            .get_open(self.inner_id as Index - 1)
+            .unwrap();
+        let parent = self.tree.parens.rank_zero(open) as NodeId;
+        Some((open, self.tree.start(parent).unwrap()))
+    }
+
+    // Returns the node whose description starts at or contains `index`.
+    fn node_at(&self, index: Index) -> Self {
+        let id = self.tree.parens.rank_zero(index - 1) as NodeId;
+        let start = self.tree.start(id).unwrap();
+        Self::new(start as NodeId, id, self.tree.clone())
+    }
+}
+impl<L, N, T> Node<L, N, T>
+where
+    T: Clone,
+{
+    pub fn tree(&self) -> T {
+        self.tree.clone()
+    }
+}
+
+impl<L, N, T> super::Node<L::Label> for Node<L, N, T>
+where
+    L: Labels,
+    N: NndOne,
+    T: ::std::ops::Deref<Target = DfudsTree<L, N>> + Clone,
+{
+    fn id(&self) -> NodeId {
+        self.id
+    }
+    fn first_child(&self) -> Option<Edge<L::Label, Self>> {
+        if self.is_leaf() {
+            return None;
+        }
+        // The first child immediately follows this node in preorder.
+        let start = self.tree.parens.succ_zero(self.inner_id as Index).unwrap() + 1;
+        let child = Self::new(start as NodeId, self.id + 1, self.tree.clone());
+        Some(Edge::new(child.label().unwrap(), child))
+    }
+    fn next_sibling(&self) -> Option<Edge<L::Label, Self>> {
+        let (open, parent_start) = self.parent_open()?;
+        if open == parent_start {
+            return None;
+        }
+        let start = self.tree.parens.get_close(open - 1).unwrap() + 1;
+        let sibling = self.node_at(start);
+        Some(Edge::new(sibling.label().unwrap(), sibling))
+    }
+    fn lower_bound_child<M, F>(&self, label: &M, f: F) -> Option<Edge<L::Label, Self>>
+    where
+        F: Fn(&M, &L::Label) -> Ordering,
+    {
+        // The labels are stored in preorder, so a probe only needs the id of the child.
+        let degree = self.degree();
+        let labels = &self.tree.labels;
+        let (Ok(k) | Err(k)) = super::binary_search_by(degree, |k| {
+            let start = self.child_start(degree, k);
+            let label_index = self.tree.parens.rank_zero(start - 1) as usize - 1;
+            f(label, &labels.get(label_index).unwrap()).reverse()
+        });
+        if k == degree {
+            return None;
+        }
+        let child = self.child_at(self.child_start(degree, k));
+        Some(Edge::new(child.label().unwrap(), child))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DfudsTree;
+    use crate::tree::balanced_parens::BalancedParensTree;
+    use crate::tree::traversal::ByteLines;
+    use crate::tree::traversal::DepthFirstTraverse;
+    use crate::tree::traversal::{numeric_words, word_lines};
+    use crate::tree::Node;
+    use crate::word::{Letters, Words};
+    use std::io;
+
+    #[test]
+    fn it_works() {
+        let lines = ByteLines::new(io::Cursor::new(b"aaa\nabc\nd"));
+        let tree = DfudsTree::new(lines.into_depth_first_traversal());
+        assert_eq!(tree.len(), 6);
+        assert_eq!(
+            Words::new(tree.root())
+                .map(|b| String::from_utf8(b).unwrap())
+                .collect::<Vec<_>>(),
+            ["aaa", "abc", "d"]
+        );
+
+        let root = tree.root();
+        assert_eq!(root.degree(), 2);
+        assert_eq!(root.child(1).unwrap().label().unwrap().value, b'd');
+        assert_eq!(root.child(1).unwrap().child_rank(), 1);
+        assert!(root.child(2).is_none());
+        assert!(root.parent().is_none());
+        assert!(root.child(1).unwrap().is_leaf());
+    }
+
+    #[test]
+    fn same_as_balanced_parens() {
+        let words = numeric_words(3000, 7);
+
+        let dfuds =
+            DfudsTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new()).build_all();
+        let bp = BalancedParensTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new())
+            .build_all();
+        assert_eq!(dfuds.len(), bp.len());
+
+        let mut dfuds_visits = dfuds.root().into_tree_traversal();
+        let mut bp_visits = bp.root().into_tree_traversal();
+        loop {
+            let visit = dfuds_visits.next();
+            assert_eq!(visit, bp_visits.next());
+            if visit.is_none() {
+                break;
+            }
+        }
+
+        for id in (0..dfuds.len() as u32 + 1).step_by(5) {
+            let node = dfuds.node(id).unwrap();
+            let bp_node = bp.node(id).unwrap();
+            assert_eq!(node.id(), id);
+            assert_eq!(node.degree(), bp_node.degree());
+            assert_eq!(node.child_rank(), bp_node.child_rank());
+            assert_eq!(
+                node.parent().map(|n| n.id()),
+                bp_node.parent().map(|n| n.id())
+            );
+            for k in 0..node.degree() {
+                assert_eq!(
+                    node.child(k).map(|n| n.id()),
+                    bp_node.child(k).map(|n| n.id())
+                );
+            }
+        }
+        for word in words.iter().step_by(11) {
+            let node = dfuds
+                .root()
+                .find_path_sorted(word.bytes(), |a, b| a.cmp(&b.value))
+                .unwrap();
+            let bp_node = bp
+                .root()
+                .find_path(word.bytes(), |a, b| *a == b.value)
+                .unwrap();
+            assert_eq!(node.id(), bp_node.id());
+        }
+        assert!(dfuds.node(dfuds.len() as u32 + 1).is_none());
+    }
+
+    #[test]
+    fn empty() {
+        let tree = DfudsTree::new(word_lines::<&str>(&[]));
+        assert!(tree.is_empty());
+        let root = tree.node(0).unwrap();
+        assert!(root.is_root());
+        assert!(root.is_leaf());
+        assert_eq!(root.degree(), 0);
+        assert_eq!(root.child_rank(), 0);
+        assert!(root.child(0).is_none());
+        assert!(root.parent().is_none());
+        assert!(tree.node(1).is_none());
+        assert_eq!(Words::new(tree.root()).count(), 0);
+
+        // The child ranks of the siblings below a word end.
+        let tree = DfudsTree::new(word_lines(&["a", "ab", "ac"]));
+        let a = tree.node(1).unwrap();
+        assert!(a.label().unwrap().end_of_word);
+        assert_eq!(a.degree(), 2);
+        assert_eq!(tree.node(3).unwrap().child_rank(), 1);
+        assert_eq!(tree.node(3).unwrap().parent().unwrap().label(), a.label());
+        assert!(tree.node(4).is_none());
+    }
+}
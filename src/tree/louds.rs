@@ -0,0 +1,410 @@
+//! Level-Order Unary Degree Sequence (LOUDS) representation.
+//!
+//! Nodes are numbered in breadth-first order (the root is `0`), and each node is represented by
+//! a `0` bit in the description of its parent. The description of a node is `0^degree 1`,
+//! and the descriptions of a virtual super root (`01`) and of all nodes in breadth-first order
+//! are concatenated.
+//!
+//! As the descriptions are terminated by `1` bits, the children are reached by `select_one` alone.
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use super::Edge;
+use super::LabelVec;
+use super::Labels;
+use super::LabelsMut;
+use super::NodeId;
+use crate::bitwise::ops::ExternalByteSize;
+use crate::bitwise::ops::NndOne;
+use crate::bitwise::ops::SelectZero;
+use crate::bitwise::BitString;
+use crate::bitwise::Index;
+use crate::bitwise::IndexedBitString;
+use crate::bitwise::Rank;
+use crate::tree::traversal::DepthFirstIter;
+use crate::tree::traversal::DepthFirstTraverse;
+
+pub struct LoudsTree<L, N = IndexedBitString> {
+    labels: L,
+    bits: N,
+}
+impl<L> LoudsTree<LabelVec<L>, IndexedBitString>
+where
+    L: Clone + Debug,
+{
+    pub fn new<T>(tree: T) -> Self
+    where
+        T: DepthFirstTraverse<Label = L>,
+    {
+        Self::new_builder(tree, LabelVec::new()).build_all()
+    }
+}
+impl<L, N> LoudsTree<L, N>
+where
+    L: LabelsMut,
+    N: NndOne + From<BitString>,
+{
+    pub fn new_builder<T>(tree: T, labels: L) -> Builder<T, L, N>
+    where
+        T: DepthFirstTraverse<Label = L::Label>,
+    {
+        Builder::new(tree, labels)
+    }
+}
+impl<L, N> LoudsTree<L, N>
+where
+    L: ExternalByteSize,
+    N: ExternalByteSize,
+{
+    pub fn external_byte_size(&self) -> u64 {
+        self.labels.external_byte_size() + self.bits.external_byte_size()
+    }
+}
+impl<L, N> LoudsTree<L, N>
+where
+    L: Labels,
+    N: NndOne,
+{
+    pub fn root(&self) -> Node<L, N, &Self> {
+        Node::new(0, 0, self)
+    }
+    pub fn to_owned_root(self) -> Node<L, N, Rc<Self>> {
+        Node::new(0, 0, Rc::new(self))
+    }
+}
+impl<L, N> LoudsTree<L, N>
+where
+    L: Labels,
+{
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.labels.len() == 0
+    }
+}
+impl<L, N> LoudsTree<L, N> {
+    pub fn labels(&self) -> &L {
+        &self.labels
+    }
+    pub fn bits(&self) -> &N {
+        &self.bits
+    }
+}
+
+pub struct Builder<T, L, N = IndexedBitString>
+where
+    T: DepthFirstTraverse,
+{
+    iter: DepthFirstIter<T>,
+    labels: L,
+    levels: Vec<Vec<T::Label>>,
+    degrees: Vec<Vec<usize>>,
+    _nnd: PhantomData<N>,
+}
+impl<T, L, N> Builder<T, L, N>
+where
+    T: DepthFirstTraverse,
+    L: LabelsMut<Label = T::Label>,
+    N: NndOne + From<BitString>,
+{
+    pub fn new(tree: T, labels: L) -> Self {
+        Builder {
+            iter: DepthFirstIter::new(tree),
+            labels,
+            levels: Vec::new(),
+            degrees: Vec::new(),
+            _nnd: PhantomData,
+        }
+    }
+    pub fn build_once(&mut self) -> bool {
+        if let Some(node) = self.iter.next() {
+            if self.levels.len() == node.level {
+                self.levels.push(Vec::new());
+                self.degrees.push(Vec::new());
+            }
+            // In depth-first order, the parent is the last visited node of the upper level.
+            if let Some(parent) = node.level.checked_sub(1) {
+                *self.degrees[parent].last_mut().unwrap() += 1;
+            }
+            self.levels[node.level].push(node.label);
+            self.degrees[node.level].push(0);
+            true
+        } else {
+            false
+        }
+    }
+    pub fn finish(mut self) -> LoudsTree<L, N> {
+        let mut bits = BitString::new();
+        let root_degree = self.levels.first().map_or(0, |l| l.len());
+        for degree in [1, root_degree]
+            .into_iter()
+            .chain(self.degrees.into_iter().flatten())
+        {
+            for _ in 0..degree {
+                bits.push(false);
+            }
+            bits.push(true);
+        }
+        for label in self.levels.into_iter().flatten() {
+            self.labels.push(label);
+        }
+        self.labels.shrink_to_fit();
+        LoudsTree {
+            labels: self.labels,
+            bits: N::from(bits),
+        }
+    }
+    pub fn build_all(mut self) -> LoudsTree<L, N> {
+        while self.build_once() {}
+        self.finish()
+    }
+}
+
+pub struct Node<L, N, T> {
+    id: NodeId,
+    inner_id: NodeId,
+    tree: T,
+    _n: PhantomData<N>,
+    _l: PhantomData<L>,
+}
+impl<L, N, T> Node<L, N, T>
+where
+    L: Labels,
+    N: NndOne,
+    T: ::std::ops::Deref<Target = LoudsTree<L, N>> + Clone,
+{
+    fn new(inner_id: NodeId, id: NodeId, tree: T) -> Self {
+        Node {
+            id,
+            inner_id,
+            tree,
+            _n: PhantomData,
+            _l: PhantomData,
+        }
+    }
+    pub fn is_root(&self) -> bool {
+        self.id == 0
+    }
+    pub fn label(&self) -> Option<L::Label> {
+        self.id
+            .checked_sub(1)
+            .and_then(|i| self.tree.labels.get(i as usize))
+    }
+    pub fn is_leaf(&self) -> bool {
+        self.degree() == 0
+    }
+    pub fn degree(&self) -> usize {
+        let start = self.children_start();
+        (self.tree.bits.succ_one(start).unwrap() - start) as usize
+    }
+
+    /// Returns the `k`-th (from 0) child.
+    pub fn child(&self, k: usize) -> Option<Self> {
+        if k >= self.degree() {
+            return None;
+        }
+        let start = self.children_start();
+        // The ids of the children are consecutive, and the first one is the number of
+        // `0` bits before `start`.
+        let first = start as NodeId - self.id - 1;
+        Some(Self::new(
+            start as NodeId + k as NodeId,
+            first + k as NodeId,
+            self.tree.clone(),
+        ))
+    }
+
+    // The description of the node starts after the `id + 1`-th `1` bit.
+    fn children_start(&self) -> Index {
+        self.tree.bits.select_one(self.id as Rank + 1).unwrap() + 1
+    }
+    fn is_node(&self, index: Index) -> bool {
+        self.tree.bits.succ_one(index) != Some(index)
+    }
+}
+impl<L, N, T> Node<L, N, T>
+where
+    L: Labels,
+    N: NndOne + SelectZero,
+    T: ::std::ops::Deref<Target = LoudsTree<L, N>> + Clone,
+{
+    pub fn parent(&self) -> Option<Self> {
+        if self.is_root() {
+            return None;
+        }
+        // The number of descriptions that end before this node (excluding the super root).
+        let id = self.tree.bits.rank_one(self.inner_id as Index) - 1;
+        let inner_id = self.tree.bits.select_zero(id + 1).unwrap();
+        Some(Self::new(
+            inner_id as NodeId,
+            id as NodeId,
+            self.tree.clone(),
+        ))
+    }
+}
+impl<L, N, T> Node<L, N, T>
+where
+    T: Clone,
+{
+    pub fn tree(&self) -> T {
+        self.tree.clone()
+    }
+}
+
+impl<L, N, T> super::Node<L::Label> for Node<L, N, T>
+where
+    L: Labels,
+    N: NndOne,
+    T: ::std::ops::Deref<Target = LoudsTree<L, N>> + Clone,
+{
+    fn id(&self) -> NodeId {
+        self.id
+    }
+    fn first_child(&self) -> Option<Edge<L::Label, Self>> {
+        let child = self.child(0)?;
+        Some(Edge::new(child.label().unwrap(), child))
+    }
+    fn next_sibling(&self) -> Option<Edge<L::Label, Self>> {
+        let next = self.inner_id as Index + 1;
+        if self.is_root() || !self.is_node(next) {
+            return None;
+        }
+        let sibling = Self::new(next as NodeId, self.id + 1, self.tree.clone());
+        Some(Edge::new(sibling.label().unwrap(), sibling))
+    }
+    fn lower_bound_child<M, F>(&self, label: &M, f: F) -> Option<Edge<L::Label, Self>>
+    where
+        F: Fn(&M, &L::Label) -> Ordering,
+    {
+        // The labels of the children are contiguous.
+        let first = self.child(0)?;
+        let labels = &self.tree.labels;
+        let (Ok(k) | Err(k)) = super::binary_search_by(self.degree(), |k| {
+            let label_index = (first.id as usize - 1) + k;
+            f(label, &labels.get(label_index).unwrap()).reverse()
+        });
+        let child = self.child(k)?;
+        Some(Edge::new(child.label().unwrap(), child))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LoudsTree;
+    use crate::bitwise::IndexedBitString;
+    use crate::tree::balanced_parens::BalancedParensTree;
+    use crate::tree::traversal::ByteLines;
+    use crate::tree::traversal::DepthFirstTraverse;
+    use crate::tree::traversal::{numeric_words, word_lines};
+    use crate::tree::Node;
+    use crate::word::{Letters, Words};
+    use std::io;
+
+    #[test]
+    fn it_works() {
+        let lines = ByteLines::new(io::Cursor::new(b"aaa\nabc\nd"));
+        let tree = LoudsTree::new(lines.into_depth_first_traversal());
+        assert_eq!(tree.len(), 6);
+        assert_eq!(
+            Words::new(tree.root())
+                .map(|b| String::from_utf8(b).unwrap())
+                .collect::<Vec<_>>(),
+            ["aaa", "abc", "d"]
+        );
+
+        let root = tree.root();
+        assert_eq!(root.degree(), 2);
+        assert_eq!(root.child(1).unwrap().label().unwrap().value, b'd');
+        assert!(root.child(2).is_none());
+        assert!(root.parent().is_none());
+        assert!(root.child(1).unwrap().is_leaf());
+    }
+
+    #[test]
+    fn same_as_balanced_parens() {
+        let words = numeric_words(3000, 7);
+
+        let louds =
+            LoudsTree::<_, IndexedBitString>::new_builder(word_lines(&words), Letters::<u8>::new())
+                .build_all();
+        let bp = BalancedParensTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new())
+            .build_all();
+        assert_eq!(louds.len(), bp.len());
+        assert_eq!(
+            Words::new(louds.root())
+                .map(|b| String::from_utf8(b).unwrap())
+                .collect::<Vec<_>>(),
+            words
+        );
+
+        let mut louds_visits = louds.root().into_tree_traversal();
+        let mut bp_visits = bp.root().into_tree_traversal();
+        loop {
+            let visit = louds_visits.next();
+            assert_eq!(visit, bp_visits.next());
+            if visit.is_none() {
+                break;
+            }
+        }
+
+        for word in words.iter().step_by(11) {
+            let node = louds
+                .root()
+                .find_path(word.bytes(), |a, b| *a == b.value)
+                .unwrap();
+            let bp_node = bp
+                .root()
+                .find_path(word.bytes(), |a, b| *a == b.value)
+                .unwrap();
+            assert_eq!(node.degree(), bp_node.degree());
+            let sorted = louds
+                .root()
+                .find_path_sorted(word.bytes(), |a, b| a.cmp(&b.value))
+                .unwrap();
+            assert_eq!(sorted.id(), node.id());
+
+            let parent = node.parent().unwrap();
+            let rank = parent.children().position(|c| c.node.id() == node.id());
+            assert_eq!(parent.child(rank.unwrap()).unwrap().id(), node.id());
+
+            let mut labels = std::iter::successors(Some(node), |n| n.parent())
+                .filter_map(|n| n.label())
+                .map(|l| l.value)
+                .collect::<Vec<_>>();
+            labels.reverse();
+            assert_eq!(labels, word.as_bytes());
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let tree = LoudsTree::new(word_lines::<&str>(&[]));
+        assert!(tree.is_empty());
+        let root = tree.root();
+        assert!(root.is_leaf());
+        assert_eq!(root.degree(), 0);
+        assert!(root.child(0).is_none());
+        assert!(root.parent().is_none());
+        assert!(root.label().is_none());
+        assert_eq!(Words::new(tree.root()).count(), 0);
+        assert!(root.find_path("a".bytes(), |a, b| *a == b.value).is_none());
+
+        // The root has a single child whose subtree is a path.
+        let tree = LoudsTree::new(word_lines(&["a", "ab"]));
+        let a = tree.root().child(0).unwrap();
+        assert!(a.label().unwrap().end_of_word);
+        assert_eq!(a.degree(), 1);
+        assert!(a.child(0).unwrap().is_leaf());
+        assert!(a
+            .child(0)
+            .unwrap()
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .is_root());
+    }
+}
@@ -1,13 +1,22 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
 use std::mem;
 
 use crate::bitwise::ops::ExternalByteSize;
+use crate::serialize::{self, FromBytes, Pod, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
 
 pub mod balanced_parens;
+pub mod dfuds;
+pub mod louds;
 pub mod traversal;
 
 pub type NodeId = u32;
 
+const LABEL_VEC_MAGIC: &[u8; 4] = b"LVEC";
+
 pub trait Node<L>: Sized {
     fn id(&self) -> NodeId;
     fn first_child(&self) -> Option<Edge<L, Self>>;
@@ -33,11 +42,50 @@ pub trait Node<L>: Sized {
         }
         last_child
     }
+
+    /// Finds the child labeled `label`, assuming that the children are sorted by their labels.
+    fn find_child_by_label<M, F>(&self, label: &M, f: F) -> Option<Edge<L, Self>>
+    where
+        F: Fn(&M, &L) -> Ordering,
+    {
+        self.lower_bound_child(label, &f)
+            .filter(|e| f(label, &e.label) == Ordering::Equal)
+    }
+
+    /// Finds the first child whose label is not less than `label`,
+    /// assuming that the children are sorted by their labels.
+    fn lower_bound_child<M, F>(&self, label: &M, f: F) -> Option<Edge<L, Self>>
+    where
+        F: Fn(&M, &L) -> Ordering,
+    {
+        self.children()
+            .find(|e| f(label, &e.label) != Ordering::Greater)
+    }
+
+    /// Same as `find_path`, but uses `find_child_by_label` at every level.
+    fn find_path_sorted<P, M, F>(&self, path: P, f: F) -> Option<Self>
+    where
+        P: Iterator<Item = M>,
+        F: Fn(&M, &L) -> Ordering,
+    {
+        let mut last_child: Option<Self> = None;
+        for label in path {
+            let node = last_child.as_ref().unwrap_or(self);
+            last_child = Some(node.find_child_by_label(&label, &f)?.node);
+        }
+        last_child
+    }
     fn into_tree_traversal(self) -> traversal::TreeTraversal<L, Self> {
         traversal::TreeTraversal::new(self)
     }
 }
 
+/// A `Node` that can also visit its children from the last one.
+pub trait ReverseNode<L>: Node<L> {
+    fn last_child(&self) -> Option<Edge<L, Self>>;
+    fn prev_sibling(&self) -> Option<Edge<L, Self>>;
+}
+
 #[derive(Debug, Clone)]
 pub struct Edge<L, N> {
     pub label: L,
@@ -49,51 +97,106 @@ impl<L, N> Edge<L, N> {
     }
 }
 
+// Same as `slice::binary_search_by` over the indices `0..len`.
+fn binary_search_by<F>(len: usize, mut f: F) -> Result<usize, usize>
+where
+    F: FnMut(usize) -> Ordering,
+{
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match f(mid) {
+            Ordering::Less => low = mid + 1,
+            Ordering::Greater => high = mid,
+            Ordering::Equal => return Ok(mid),
+        }
+    }
+    Err(low)
+}
+
 pub trait Labels {
     type Label;
-    fn push(&mut self, label: Self::Label);
     fn get(&self, index: usize) -> Option<Self::Label>;
     fn len(&self) -> usize;
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
+}
+
+pub trait LabelsMut: Labels {
+    fn push(&mut self, label: Self::Label);
     fn shrink_to_fit(&mut self) {}
 }
 
 #[derive(Debug, Clone)]
-pub struct LabelVec<T>(Vec<T>);
-impl<T> LabelVec<T> {
+pub struct LabelVec<T: Clone + Debug + 'static, S: Storage = Owned>(S::Slice<T>);
+impl<T: Clone + Debug> LabelVec<T> {
     pub fn new() -> Self {
         LabelVec(Vec::new())
     }
 }
-impl<T> Default for LabelVec<T> {
+impl<T: Clone + Debug> Default for LabelVec<T> {
     fn default() -> Self {
         Self::new()
     }
 }
-impl<T> ExternalByteSize for LabelVec<T>
+impl<T, S> ExternalByteSize for LabelVec<T, S>
 where
-    T: Sized,
+    T: Clone + Debug,
+    S: Storage,
 {
     fn external_byte_size(&self) -> u64 {
         mem::size_of_val(&self.0.len()) as u64 + mem::size_of::<T>() as u64 * self.0.len() as u64
     }
 }
-impl<T> Labels for LabelVec<T>
+impl<T, S> WriteTo for LabelVec<T, S>
 where
-    T: Clone,
+    T: Pod + Debug,
+    S: Storage,
 {
-    type Label = T;
-    fn push(&mut self, label: Self::Label) {
-        self.0.push(label);
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, LABEL_VEC_MAGIC)?;
+        serialize::write_slice(writer, &self.0)
+    }
+}
+impl<T> ReadFrom for LabelVec<T>
+where
+    T: Pod + Debug,
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, LABEL_VEC_MAGIC)?;
+        serialize::read_vec(&mut reader).map(LabelVec)
+    }
+}
+impl<'a, T> FromBytes<'a> for LabelVec<T, Borrowed<'a>>
+where
+    T: Pod + Debug,
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, LABEL_VEC_MAGIC)?;
+        serialize::view_slice(bytes).map(LabelVec)
     }
+}
+impl<T, S> Labels for LabelVec<T, S>
+where
+    T: Clone + Debug,
+    S: Storage,
+{
+    type Label = T;
     fn get(&self, index: usize) -> Option<Self::Label> {
         self.0.get(index).cloned()
     }
     fn len(&self) -> usize {
         self.0.len()
     }
+}
+impl<T> LabelsMut for LabelVec<T>
+where
+    T: Clone + Debug,
+{
+    fn push(&mut self, label: Self::Label) {
+        self.0.push(label);
+    }
     fn shrink_to_fit(&mut self) {
         self.0.shrink_to_fit();
     }
@@ -111,8 +214,13 @@ where
     N: Node<L>,
 {
     fn new(node: &N) -> Self {
+        Self::starting_at(node.first_child())
+    }
+
+    // Iterates over `child` and its following siblings.
+    pub(crate) fn starting_at(child: Option<Edge<L, N>>) -> Self {
         Children {
-            child: node.first_child(),
+            child,
             _l: PhantomData,
         }
     }
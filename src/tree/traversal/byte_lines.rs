@@ -43,6 +43,29 @@ where
     }
 }
 
+/// Returns `count` multiples of `step` as decimal strings in lexicographic order.
+#[cfg(test)]
+pub(crate) fn numeric_words(count: usize, step: usize) -> Vec<String> {
+    let mut words = (0..count)
+        .map(|i| format!("{}", i * step))
+        .collect::<Vec<_>>();
+    words.sort();
+    words
+}
+
+/// Makes a traversal over the sorted `words`.
+#[cfg(test)]
+pub(crate) fn word_lines<W: AsRef<str>>(
+    words: &[W],
+) -> DepthFirstTraversal<u8, ByteLines<io::Cursor<Vec<u8>>>> {
+    let input = words
+        .iter()
+        .map(|w| w.as_ref())
+        .collect::<Vec<_>>()
+        .join("\n");
+    ByteLines::new(io::Cursor::new(input.into_bytes())).into_depth_first_traversal()
+}
+
 #[cfg(test)]
 mod test {
     use super::super::VisitNode;
@@ -2,6 +2,9 @@ pub use self::byte_lines::ByteLines;
 pub use self::tree_traversal::PatriciaTreeTraversal;
 pub use self::tree_traversal::TreeTraversal;
 
+#[cfg(test)]
+pub(crate) use self::byte_lines::{numeric_words, word_lines};
+
 mod byte_lines;
 mod tree_traversal;
 
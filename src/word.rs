@@ -1,14 +1,38 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+use std::iter;
 use std::mem;
+use std::ops::{Bound, RangeBounds};
+use std::slice;
+use std::vec;
 
 use crate::bitwise::ops::ExternalByteSize;
+use crate::bitwise::ops::NndOne;
+use crate::bitwise::ops::RankBit;
+use crate::bitwise::ops::SelectOne;
 use crate::bitwise::BitString;
 use crate::bitwise::Index;
+use crate::bitwise::IndexedBitString;
+use crate::bitwise::Rank;
+use crate::serialize::{self, FromBytes, Pod, ReadFrom, WriteTo};
+use crate::storage::{Borrowed, Owned, Storage};
+use crate::tree::balanced_parens::BalancedParensTree;
+use crate::tree::balanced_parens::Node as BalancedParensNode;
 use crate::tree::traversal;
 use crate::tree::traversal::DepthFirstIter;
 use crate::tree::traversal::DepthFirstTraverse;
+use crate::tree::Children;
 use crate::tree::Edge;
 use crate::tree::Labels;
+use crate::tree::LabelsMut;
 use crate::tree::Node;
+use crate::tree::NodeId;
+use crate::tree::ReverseNode;
+
+const LETTERS_MAGIC: &[u8; 4] = b"LTRS";
+const LETTERS_WITH_VALUES_MAGIC: &[u8; 4] = b"LTRV";
+const PATRICIA_LETTERS_MAGIC: &[u8; 4] = b"PLTR";
 
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct Letter<T> {
@@ -22,26 +46,27 @@ impl<T> Letter<T> {
 }
 
 #[derive(Debug, Clone)]
-pub struct Letters<T> {
-    pub end_of_words: BitString,
-    pub values: Vec<T>,
+pub struct Letters<T: Clone + Debug + 'static, S: Storage = Owned> {
+    pub end_of_words: IndexedBitString<S>,
+    pub values: S::Slice<T>,
 }
-impl<T> Letters<T> {
+impl<T: Clone + Debug> Letters<T> {
     pub fn new() -> Self {
         Letters {
-            end_of_words: BitString::new(),
+            end_of_words: IndexedBitString::new(),
             values: Vec::new(),
         }
     }
 }
-impl<T> Default for Letters<T> {
+impl<T: Clone + Debug> Default for Letters<T> {
     fn default() -> Self {
         Self::new()
     }
 }
-impl<T> ExternalByteSize for Letters<T>
+impl<T, S> ExternalByteSize for Letters<T, S>
 where
-    T: Sized,
+    T: Clone + Debug,
+    S: Storage,
 {
     fn external_byte_size(&self) -> u64 {
         self.end_of_words.external_byte_size()
@@ -49,15 +74,65 @@ where
             + mem::size_of::<T>() as u64 * self.values.len() as u64
     }
 }
-impl<T> Labels for Letters<T>
+impl<T, S> WriteTo for Letters<T, S>
 where
-    T: Clone,
+    T: Pod + Debug,
+    S: Storage,
 {
-    type Label = Letter<T>;
-    fn push(&mut self, label: Self::Label) {
-        self.end_of_words.push(label.end_of_word);
-        self.values.push(label.value);
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, LETTERS_MAGIC)?;
+        self.end_of_words.write_to(writer)?;
+        serialize::write_slice(writer, &self.values)
     }
+}
+impl<T> ReadFrom for Letters<T>
+where
+    T: Pod + Debug,
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, LETTERS_MAGIC)?;
+        Letters {
+            end_of_words: IndexedBitString::read_from(&mut reader)?,
+            values: serialize::read_vec(&mut reader)?,
+        }
+        .validate()
+    }
+}
+impl<'a, T> FromBytes<'a> for Letters<T, Borrowed<'a>>
+where
+    T: Pod + Debug,
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, LETTERS_MAGIC)?;
+        Letters {
+            end_of_words: IndexedBitString::from_bytes(bytes)?,
+            values: serialize::view_slice(bytes)?,
+        }
+        .validate()
+    }
+}
+impl<T, S> Letters<T, S>
+where
+    T: Clone + Debug,
+    S: Storage,
+{
+    fn validate(self) -> io::Result<Self> {
+        if self.end_of_words.len() != self.values.len() as Index {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected letter count: end_of_words={}, values={}",
+                self.end_of_words.len(),
+                self.values.len()
+            )));
+        }
+        Ok(self)
+    }
+}
+impl<T, S> Labels for Letters<T, S>
+where
+    T: Clone + Debug,
+    S: Storage,
+{
+    type Label = Letter<T>;
     fn get(&self, index: usize) -> Option<Self::Label> {
         self.values
             .get(index)
@@ -67,32 +142,452 @@ where
     fn len(&self) -> usize {
         self.values.len()
     }
+}
+impl<T> LabelsMut for Letters<T>
+where
+    T: Clone + Debug,
+{
+    fn push(&mut self, label: Self::Label) {
+        self.end_of_words.push(label.end_of_word);
+        self.values.push(label.value);
+    }
     fn shrink_to_fit(&mut self) {
         self.end_of_words.shrink_to_fit();
         self.values.shrink_to_fit();
     }
 }
+impl<T, S> AsRef<Letters<T, S>> for Letters<T, S>
+where
+    T: Clone + Debug,
+    S: Storage,
+{
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
 
-#[derive(Debug)]
-pub struct Words<T, N> {
+/// `Letters` with a value for each word.
+///
+/// The `i`-th value belongs to the `i`-th word in preorder (see `BalancedParensTree::word_id`).
+#[derive(Debug, Clone)]
+pub struct LettersWithValues<
+    T: Clone + Debug + 'static,
+    V: Clone + Debug + 'static,
+    S: Storage = Owned,
+> {
+    pub letters: Letters<T, S>,
+    pub values: S::Slice<V>,
+}
+impl<T, V> LettersWithValues<T, V>
+where
+    T: Clone + Debug,
+    V: Clone + Debug,
+{
+    /// Attaches `values` to the words of `letters`.
+    ///
+    /// Returns an `InvalidData` error if the number of the words differs from the number of `values`.
+    pub fn new(letters: Letters<T>, values: Vec<V>) -> io::Result<Self> {
+        LettersWithValues { letters, values }.validate()
+    }
+}
+impl<T, V, S> ExternalByteSize for LettersWithValues<T, V, S>
+where
+    T: Clone + Debug,
+    V: Clone + Debug,
+    S: Storage,
+{
+    fn external_byte_size(&self) -> u64 {
+        self.letters.external_byte_size()
+            + mem::size_of_val(&self.values.len()) as u64
+            + mem::size_of::<V>() as u64 * self.values.len() as u64
+    }
+}
+impl<T, V, S> WriteTo for LettersWithValues<T, V, S>
+where
+    T: Pod + Debug,
+    V: Pod + Debug,
+    S: Storage,
+{
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, LETTERS_WITH_VALUES_MAGIC)?;
+        self.letters.write_to(writer)?;
+        serialize::write_slice(writer, &self.values)
+    }
+}
+impl<T, V> ReadFrom for LettersWithValues<T, V>
+where
+    T: Pod + Debug,
+    V: Pod + Debug,
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, LETTERS_WITH_VALUES_MAGIC)?;
+        LettersWithValues {
+            letters: Letters::read_from(&mut reader)?,
+            values: serialize::read_vec(&mut reader)?,
+        }
+        .validate()
+    }
+}
+impl<'a, T, V> FromBytes<'a> for LettersWithValues<T, V, Borrowed<'a>>
+where
+    T: Pod + Debug,
+    V: Pod + Debug,
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, LETTERS_WITH_VALUES_MAGIC)?;
+        LettersWithValues {
+            letters: Letters::from_bytes(bytes)?,
+            values: serialize::view_slice(bytes)?,
+        }
+        .validate()
+    }
+}
+impl<T, V, S> LettersWithValues<T, V, S>
+where
+    T: Clone + Debug,
+    V: Clone + Debug,
+    S: Storage,
+{
+    fn validate(self) -> io::Result<Self> {
+        let words = self.letters.end_of_words.count_ones();
+        if words != self.values.len() as Rank {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected value count: words={}, values={}",
+                words,
+                self.values.len()
+            )));
+        }
+        Ok(self)
+    }
+}
+impl<T, V, S> Labels for LettersWithValues<T, V, S>
+where
+    T: Clone + Debug,
+    V: Clone + Debug,
+    S: Storage,
+{
+    type Label = Letter<T>;
+    fn get(&self, index: usize) -> Option<Self::Label> {
+        self.letters.get(index)
+    }
+    fn len(&self) -> usize {
+        self.letters.len()
+    }
+}
+impl<T, V, S> AsRef<Letters<T, S>> for LettersWithValues<T, V, S>
+where
+    T: Clone + Debug,
+    V: Clone + Debug,
+    S: Storage,
+{
+    fn as_ref(&self) -> &Letters<T, S> {
+        &self.letters
+    }
+}
+
+/// Path-compressed letters: each node is labeled by a sequence of values.
+///
+/// The values of all nodes are concatenated into `values`, and `offsets` holds the end offset of
+/// the values of each node.
+#[derive(Debug, Clone)]
+pub struct PatriciaLetters<T: Clone + Debug + 'static, S: Storage = Owned> {
+    pub end_of_words: BitString<u64, S>,
+    pub offsets: S::Slice<u64>,
+    pub values: S::Slice<T>,
+}
+impl<T: Clone + Debug> PatriciaLetters<T> {
+    pub fn new() -> Self {
+        PatriciaLetters {
+            end_of_words: BitString::new(),
+            offsets: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+impl<T: Clone + Debug> Default for PatriciaLetters<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T, S> PatriciaLetters<T, S>
+where
+    T: Clone + Debug,
+    S: Storage,
+{
+    /// Returns the values of the `index`-th node.
+    pub fn values(&self, index: usize) -> Option<&[T]> {
+        let end = *self.offsets.get(index)? as usize;
+        let start = index.checked_sub(1).map_or(0, |i| self.offsets[i] as usize);
+        Some(&self.values[start..end])
+    }
+    fn validate(self) -> io::Result<Self> {
+        if self.end_of_words.len() != self.offsets.len() as Index {
+            return Err(serialize::invalid_data(format!(
+                "Unexpected letter count: end_of_words={}, offsets={}",
+                self.end_of_words.len(),
+                self.offsets.len()
+            )));
+        }
+        if self.offsets.windows(2).any(|w| w[0] > w[1])
+            || self.offsets.last().map_or(0, |&o| o) != self.values.len() as u64
+        {
+            return Err(serialize::invalid_data("Malformed offsets"));
+        }
+        Ok(self)
+    }
+}
+impl<T, S> ExternalByteSize for PatriciaLetters<T, S>
+where
+    T: Clone + Debug,
+    S: Storage,
+{
+    fn external_byte_size(&self) -> u64 {
+        self.end_of_words.external_byte_size()
+            + mem::size_of_val(&self.offsets.len()) as u64
+            + mem::size_of::<u64>() as u64 * self.offsets.len() as u64
+            + mem::size_of_val(&self.values.len()) as u64
+            + mem::size_of::<T>() as u64 * self.values.len() as u64
+    }
+}
+impl<T, S> WriteTo for PatriciaLetters<T, S>
+where
+    T: Pod + Debug,
+    S: Storage,
+{
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        serialize::write_header(writer, PATRICIA_LETTERS_MAGIC)?;
+        self.end_of_words.write_to(writer)?;
+        serialize::write_slice(writer, &self.offsets)?;
+        serialize::write_slice(writer, &self.values)
+    }
+}
+impl<T> ReadFrom for PatriciaLetters<T>
+where
+    T: Pod + Debug,
+{
+    fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        serialize::read_header(&mut reader, PATRICIA_LETTERS_MAGIC)?;
+        PatriciaLetters {
+            end_of_words: BitString::read_from(&mut reader)?,
+            offsets: serialize::read_vec(&mut reader)?,
+            values: serialize::read_vec(&mut reader)?,
+        }
+        .validate()
+    }
+}
+impl<'a, T> FromBytes<'a> for PatriciaLetters<T, Borrowed<'a>>
+where
+    T: Pod + Debug,
+{
+    fn from_bytes(bytes: &mut &'a [u8]) -> io::Result<Self> {
+        serialize::read_header(bytes, PATRICIA_LETTERS_MAGIC)?;
+        PatriciaLetters {
+            end_of_words: BitString::from_bytes(bytes)?,
+            offsets: serialize::view_slice(bytes)?,
+            values: serialize::view_slice(bytes)?,
+        }
+        .validate()
+    }
+}
+impl<T, S> Labels for PatriciaLetters<T, S>
+where
+    T: Clone + Debug,
+    S: Storage,
+{
+    type Label = Letter<Vec<T>>;
+    fn get(&self, index: usize) -> Option<Self::Label> {
+        self.values(index)
+            .map(|v| Letter::new(self.end_of_words.get(index as Index).unwrap(), v.to_vec()))
+    }
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+}
+impl<T> LabelsMut for PatriciaLetters<T>
+where
+    T: Clone + Debug,
+{
+    fn push(&mut self, label: Self::Label) {
+        self.end_of_words.push(label.end_of_word);
+        self.values.extend(label.value);
+        self.offsets.push(self.values.len() as u64);
+    }
+    fn shrink_to_fit(&mut self) {
+        self.end_of_words.shrink_to_fit();
+        self.offsets.shrink_to_fit();
+        self.values.shrink_to_fit();
+    }
+}
+
+impl<L, T, N, S> BalancedParensTree<L, N, S>
+where
+    L: Labels<Label = Letter<T>> + AsRef<Letters<T, S>>,
+    T: Clone + Debug + Ord + 'static,
+    N: NndOne,
+    S: Storage,
+{
+    /// Returns the number of the words that precede `word` in preorder.
+    pub fn word_id(&self, word: &[T]) -> Option<u64> {
+        let node = self
+            .root()
+            .find_path_sorted(word.iter(), |a, b| (*a).cmp(&b.value))?;
+        let index = node.id() as Index - 1;
+        let end_of_words = &self.labels().as_ref().end_of_words;
+        if end_of_words.get(index)? {
+            Some(end_of_words.rank_one(index) - 1)
+        } else {
+            None
+        }
+    }
+    /// Returns the word whose id is `id` (see `word_id`).
+    pub fn word(&self, id: u64) -> Option<Vec<T>> {
+        let index = self
+            .labels()
+            .as_ref()
+            .end_of_words
+            .select_one(id.checked_add(1)?)?;
+        let node = self.node(index as NodeId + 1)?;
+        let ancestors = node.ancestors();
+        let mut word = iter::once(node)
+            .chain(ancestors)
+            .filter_map(|n| n.label())
+            .map(|l| l.value)
+            .collect::<Vec<_>>();
+        word.reverse();
+        Some(word)
+    }
+}
+
+impl<T, N> BalancedParensTree<Letters<T>, N>
+where
+    T: Clone + Debug,
+{
+    /// Attaches `values` to the words (see `LettersWithValues::new`).
+    pub fn with_values<V>(
+        self,
+        values: Vec<V>,
+    ) -> io::Result<BalancedParensTree<LettersWithValues<T, V>, N>>
+    where
+        V: Clone + Debug,
+    {
+        self.try_map_labels(|letters| LettersWithValues::new(letters, values))
+    }
+}
+
+impl<T, V, N, S> BalancedParensTreeWithValues<T, V, N, S>
+where
+    T: Clone + Debug + Ord,
+    V: Clone + Debug,
+    N: NndOne,
+    S: Storage,
+{
+    pub fn get(&self, word: &[T]) -> Option<&V> {
+        let id = self.word_id(word)?;
+        self.labels().values.get(id as usize)
+    }
+
+    /// Returns the words and their values in preorder.
+    pub fn iter(&self) -> WordValues<'_, T, V, N, S> {
+        Words::new(self.root()).zip(self.labels().values.iter())
+    }
+}
+
+type BalancedParensTreeWithValues<T, V, N, S> =
+    BalancedParensTree<LettersWithValues<T, V, S>, N, S>;
+
+pub type WordValues<'a, T, V, N, S> = iter::Zip<
+    Words<
+        T,
+        BalancedParensNode<
+            LettersWithValues<T, V, S>,
+            N,
+            &'a BalancedParensTreeWithValues<T, V, N, S>,
+        >,
+    >,
+    slice::Iter<'a, V>,
+>;
+
+pub struct Words<T, N>
+where
+    N: Node<Letter<T>>,
+{
     buf: Vec<T>,
-    stack: Vec<Vec<Edge<Letter<T>, N>>>,
+    stack: Vec<Children<N, Letter<T>>>,
+    pending: Option<Vec<T>>,
 }
 impl<T, N> Words<T, N>
 where
     N: Node<Letter<T>>,
 {
     pub fn new(root: N) -> Self {
+        Self::with_prefix(root, Vec::new(), None)
+    }
+
+    // Enumerates the words below `node`, which is reached by `prefix`.
+    // `pending` is `prefix` itself if it is a word.
+    fn with_prefix(node: N, prefix: Vec<T>, pending: Option<Vec<T>>) -> Self {
+        Words {
+            buf: prefix,
+            stack: vec![node.children()],
+            pending,
+        }
+    }
+
+    /// Returns the words in `range`, assuming that the children of every node are sorted.
+    ///
+    /// The first word is reached by following `range`'s start bound from `root`
+    /// with `Node::lower_bound_child`.
+    pub fn range<R, K>(root: N, range: R) -> WordRange<T, N>
+    where
+        T: Ord + Clone,
+        R: RangeBounds<K>,
+        K: AsRef<[T]>,
+    {
         let mut words = Words {
             buf: Vec::new(),
             stack: Vec::new(),
+            pending: None,
         };
-        let mut children = root.children().collect::<Vec<_>>();
-        if !children.is_empty() {
-            children.reverse();
-            words.stack.push(children);
+        let start = match range.start_bound() {
+            Bound::Included(s) | Bound::Excluded(s) => s.as_ref(),
+            Bound::Unbounded => &[],
+        };
+        let mut node = root;
+        let mut is_word = false;
+        let mut children = None;
+        for value in start {
+            match node.lower_bound_child(value, cmp_letter) {
+                Some(e) if e.label.value == *value => {
+                    words
+                        .stack
+                        .push(Children::starting_at(e.node.next_sibling()));
+                    words.buf.push(e.label.value);
+                    node = e.node;
+                    is_word = e.label.end_of_word;
+                }
+                e => {
+                    // Every word from `e` is greater than `start`.
+                    children = Some(Children::starting_at(e));
+                    break;
+                }
+            }
+        }
+        if is_word && words.buf.len() == start.len() {
+            if let Bound::Included(_) = range.start_bound() {
+                words.pending = Some(words.buf.clone());
+            }
         }
         words
+            .stack
+            .push(children.unwrap_or_else(|| node.children()));
+        WordRange {
+            words,
+            end: match range.end_bound() {
+                Bound::Included(e) => Bound::Included(e.as_ref().to_vec()),
+                Bound::Excluded(e) => Bound::Excluded(e.as_ref().to_vec()),
+                Bound::Unbounded => Bound::Unbounded,
+            },
+        }
     }
 }
 impl<T, N> Iterator for Words<T, N>
@@ -102,14 +597,14 @@ where
 {
     type Item = Vec<T>;
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(word) = self.pending.take() {
+            return Some(word);
+        }
         while let Some(mut children) = self.stack.pop() {
-            if let Some(e) = children.pop() {
+            if let Some(e) = children.next() {
                 self.stack.push(children);
                 self.buf.push(e.label.value);
-
-                let mut grand_children = e.node.children().collect::<Vec<_>>();
-                grand_children.reverse();
-                self.stack.push(grand_children);
+                self.stack.push(e.node.children());
 
                 if e.label.end_of_word {
                     let word = self.buf.clone();
@@ -119,11 +614,325 @@ where
                 self.buf.pop();
             }
         }
-        debug_assert!(self.buf.is_empty());
         None
     }
 }
 
+/// Enumerates the words in the reverse order of `Words`.
+pub struct WordsRev<T, N> {
+    buf: Vec<T>,
+    stack: Vec<(Edge<Letter<T>, N>, bool)>,
+}
+impl<T, N> WordsRev<T, N>
+where
+    N: ReverseNode<Letter<T>>,
+{
+    pub fn new(root: N) -> Self {
+        WordsRev {
+            buf: Vec::new(),
+            stack: root.last_child().map(|e| (e, false)).into_iter().collect(),
+        }
+    }
+}
+impl<T, N> Iterator for WordsRev<T, N>
+where
+    N: ReverseNode<Letter<T>>,
+    T: Clone,
+{
+    type Item = Vec<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((e, visited)) = self.stack.pop() {
+            if visited {
+                // A word follows the words that it is a prefix of.
+                let word = e.label.end_of_word.then(|| self.buf.clone());
+                self.buf.pop();
+                if let Some(sibling) = e.node.prev_sibling() {
+                    self.stack.push((sibling, false));
+                }
+                if word.is_some() {
+                    return word;
+                }
+            } else {
+                self.buf.push(e.label.value.clone());
+                let child = e.node.last_child();
+                self.stack.push((e, true));
+                if let Some(child) = child {
+                    self.stack.push((child, false));
+                }
+            }
+        }
+        None
+    }
+}
+
+pub struct WordRange<T, N>
+where
+    N: Node<Letter<T>>,
+{
+    words: Words<T, N>,
+    end: Bound<Vec<T>>,
+}
+impl<T, N> Iterator for WordRange<T, N>
+where
+    N: Node<Letter<T>>,
+    T: Ord + Clone,
+{
+    type Item = Vec<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let word = self.words.next()?;
+        let in_range = match &self.end {
+            Bound::Included(end) => word <= *end,
+            Bound::Excluded(end) => word < *end,
+            Bound::Unbounded => true,
+        };
+        if in_range {
+            Some(word)
+        } else {
+            self.words.stack.clear();
+            None
+        }
+    }
+}
+
+/// A deterministic automaton that `WordTrie::search` runs over the words.
+pub trait Automaton<T> {
+    type State: Clone;
+    fn start(&self) -> Self::State;
+    fn is_match(&self, state: &Self::State) -> bool;
+
+    /// Returns `false` if no word can be accepted from `state`, to prune the subtree.
+    fn can_match(&self, _state: &Self::State) -> bool {
+        true
+    }
+    fn accept(&self, state: &Self::State, value: &T) -> Self::State;
+}
+
+/// An automaton that accepts the words within `max_distance` Levenshtein distance from `query`.
+///
+/// A state is the edit distances between the input and the prefixes of `query`.
+#[derive(Debug, Clone)]
+pub struct Levenshtein<T> {
+    query: Vec<T>,
+    max_distance: usize,
+}
+impl<T> Levenshtein<T> {
+    pub fn new(query: Vec<T>, max_distance: usize) -> Self {
+        Levenshtein {
+            query,
+            max_distance,
+        }
+    }
+}
+impl<T: PartialEq> Automaton<T> for Levenshtein<T> {
+    type State = Vec<usize>;
+    fn start(&self) -> Self::State {
+        (0..=self.query.len()).collect()
+    }
+    fn is_match(&self, state: &Self::State) -> bool {
+        *state.last().unwrap() <= self.max_distance
+    }
+    fn can_match(&self, state: &Self::State) -> bool {
+        // The distances never decrease below the minimum of the row.
+        state.iter().min().is_some_and(|&d| d <= self.max_distance)
+    }
+    fn accept(&self, state: &Self::State, value: &T) -> Self::State {
+        let mut row = Vec::with_capacity(state.len());
+        row.push(state[0] + 1);
+        for (i, q) in self.query.iter().enumerate() {
+            let replace = state[i] + usize::from(q != value);
+            row.push(replace.min(state[i + 1] + 1).min(row[i] + 1));
+        }
+        row
+    }
+}
+
+pub struct AutomatonSearch<T, N, A: Automaton<T>> {
+    automaton: A,
+    buf: Vec<T>,
+    // `states[i]` is the state after accepting `buf[..i]`.
+    states: Vec<A::State>,
+    stack: Vec<(Edge<Letter<T>, N>, usize)>,
+}
+impl<T, N, A> AutomatonSearch<T, N, A>
+where
+    N: Node<Letter<T>>,
+    T: Clone,
+    A: Automaton<T>,
+{
+    /// Returns the next word with the state of the automaton after accepting it.
+    pub fn next_with_state(&mut self) -> Option<(Vec<T>, A::State)> {
+        while let Some((e, depth)) = self.stack.pop() {
+            self.states.truncate(depth + 1);
+            self.buf.truncate(depth);
+
+            let state = self.automaton.accept(&self.states[depth], &e.label.value);
+            if !self.automaton.can_match(&state) {
+                continue;
+            }
+            let mut children = e
+                .node
+                .children()
+                .map(|c| (c, depth + 1))
+                .collect::<Vec<_>>();
+            children.reverse();
+            self.stack.extend(children);
+
+            self.buf.push(e.label.value);
+            self.states.push(state);
+            let state = self.states.last().unwrap();
+            if e.label.end_of_word && self.automaton.is_match(state) {
+                return Some((self.buf.clone(), state.clone()));
+            }
+        }
+        None
+    }
+}
+impl<T, N, A> Iterator for AutomatonSearch<T, N, A>
+where
+    N: Node<Letter<T>>,
+    T: Clone,
+    A: Automaton<T>,
+{
+    type Item = Vec<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_state().map(|(word, _)| word)
+    }
+}
+
+pub struct LevenshteinSearch<T: PartialEq, N>(AutomatonSearch<T, N, Levenshtein<T>>);
+impl<T, N> Iterator for LevenshteinSearch<T, N>
+where
+    N: Node<Letter<T>>,
+    T: Clone + PartialEq,
+{
+    type Item = (Vec<T>, usize);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next_with_state()
+            .map(|(word, row)| (word, *row.last().unwrap()))
+    }
+}
+
+/// Dictionary lookups on the root of a trie whose edges are labeled by `Letter`s.
+///
+/// The children are looked up by `Node::find_child_by_label`, so their letters must be sorted.
+pub trait WordTrie<T>: Node<Letter<T>> {
+    fn contains(&self, word: &[T]) -> bool
+    where
+        T: Ord,
+    {
+        self.common_prefix_search(word).last() == Some(word.len())
+    }
+
+    /// Returns the length of the longest word that is a prefix of `text`.
+    fn longest_prefix_of(&self, text: &[T]) -> Option<usize>
+    where
+        T: Ord,
+    {
+        self.common_prefix_search(text).last()
+    }
+
+    /// Returns the lengths of the words that are prefixes of `text`, in ascending order.
+    fn common_prefix_search<'a>(&self, text: &'a [T]) -> CommonPrefixSearch<'a, T, Self>
+    where
+        T: Ord,
+    {
+        CommonPrefixSearch {
+            text,
+            len: 0,
+            edge: text
+                .first()
+                .and_then(|v| self.find_child_by_label(v, cmp_letter)),
+        }
+    }
+
+    /// Returns the words that start with `prefix`.
+    fn predictive_search(self, prefix: &[T]) -> Words<T, Self>
+    where
+        T: Clone + Ord,
+    {
+        let mut node = self;
+        let mut is_word = false;
+        for value in prefix {
+            match node.find_child_by_label(value, cmp_letter) {
+                None => {
+                    return Words {
+                        buf: Vec::new(),
+                        stack: Vec::new(),
+                        pending: None,
+                    }
+                }
+                Some(e) => {
+                    node = e.node;
+                    is_word = e.label.end_of_word;
+                }
+            }
+        }
+        Words::with_prefix(node, prefix.to_vec(), is_word.then(|| prefix.to_vec()))
+    }
+
+    /// Returns the words accepted by `automaton`.
+    fn search<A>(&self, automaton: A) -> AutomatonSearch<T, Self, A>
+    where
+        A: Automaton<T>,
+    {
+        let start = automaton.start();
+        let mut children = Vec::new();
+        if automaton.can_match(&start) {
+            children.extend(self.children().map(|e| (e, 0)));
+            children.reverse();
+        }
+        AutomatonSearch {
+            automaton,
+            buf: Vec::new(),
+            states: vec![start],
+            stack: children,
+        }
+    }
+
+    /// Returns the words within `max_distance` Levenshtein distance from `query`, with the distances.
+    fn levenshtein_search(&self, query: &[T], max_distance: usize) -> LevenshteinSearch<T, Self>
+    where
+        T: Clone + PartialEq,
+    {
+        LevenshteinSearch(self.search(Levenshtein::new(query.to_vec(), max_distance)))
+    }
+}
+impl<T, N> WordTrie<T> for N where N: Node<Letter<T>> {}
+
+pub struct CommonPrefixSearch<'a, T, N>
+where
+    N: Node<Letter<T>>,
+{
+    text: &'a [T],
+    len: usize,
+    edge: Option<Edge<Letter<T>, N>>,
+}
+impl<'a, T, N> Iterator for CommonPrefixSearch<'a, T, N>
+where
+    N: Node<Letter<T>>,
+    T: Ord,
+{
+    type Item = usize;
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(e) = self.edge.take() {
+            self.len += 1;
+            self.edge = self
+                .text
+                .get(self.len)
+                .and_then(|v| e.node.find_child_by_label(v, cmp_letter));
+            if e.label.end_of_word {
+                return Some(self.len);
+            }
+        }
+        None
+    }
+}
+
+fn cmp_letter<T: Ord>(value: &T, letter: &Letter<T>) -> Ordering {
+    value.cmp(&letter.value)
+}
+
 pub struct DepthFirstTraversal<T, W> {
     buf: Vec<T>,
     path: Vec<(Option<Letter<T>>, usize)>,
@@ -181,3 +990,578 @@ where
         }
     }
 }
+
+type PatriciaEdge<T, N> = Edge<Letter<Vec<T>>, N>;
+
+/// Traverses the subtree of a node, merging every non-terminal node that has exactly one child
+/// with the child.
+///
+/// The pending siblings on the current path are the only nodes held at a time.
+pub struct PatriciaTraversal<T, N> {
+    stack: Vec<(PatriciaEdge<T, N>, usize, usize)>,
+}
+impl<T, N> PatriciaTraversal<T, N>
+where
+    N: Node<Letter<T>>,
+{
+    pub fn new(root: N) -> Self {
+        PatriciaTraversal {
+            stack: root
+                .first_child()
+                .into_iter()
+                .map(|e| (Self::edge(e), 0, 0))
+                .collect(),
+        }
+    }
+
+    fn edge(e: Edge<Letter<T>, N>) -> PatriciaEdge<T, N> {
+        Edge::new(
+            Letter::new(e.label.end_of_word, vec![e.label.value]),
+            e.node,
+        )
+    }
+}
+impl<T, N> DepthFirstTraverse for PatriciaTraversal<T, N>
+where
+    N: Node<Letter<T>>,
+{
+    type Label = Letter<Vec<T>>;
+    fn next(&mut self) -> Option<traversal::VisitNode<Self::Label>> {
+        let (mut edge, level, nth_child) = self.stack.pop()?;
+        if let Some(s) = edge.node.next_sibling() {
+            self.stack.push((Self::edge(s), level, nth_child + 1));
+        }
+        while !edge.label.end_of_word {
+            match edge.node.first_child() {
+                Some(c) if c.node.next_sibling().is_none() => {
+                    edge.label.value.push(c.label.value);
+                    edge.label.end_of_word = c.label.end_of_word;
+                    edge.node = c.node;
+                }
+                _ => break,
+            }
+        }
+        if let Some(c) = edge.node.first_child() {
+            self.stack.push((Self::edge(c), level + 1, 0));
+        }
+        Some(traversal::VisitNode::new(edge.label, level, nth_child))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Automaton, Letters, LettersWithValues, PatriciaLetters, PatriciaTraversal, WordTrie, Words,
+        WordsRev,
+    };
+    use crate::serialize::{self, FromBytes, ReadFrom, WriteTo};
+    use crate::storage::Borrowed;
+    use crate::tree::balanced_parens::BalancedParensTree;
+    use crate::tree::dfuds::DfudsTree;
+    use crate::tree::louds::LoudsTree;
+    use crate::tree::traversal::ByteLines;
+    use crate::tree::traversal::{numeric_words, word_lines};
+    use std::cell::Cell;
+    use std::io;
+    use std::ops::Bound;
+
+    #[test]
+    fn word_trie() {
+        let input = b"a\nab\nabc\nabd\nb\nbcd";
+        let lines = ByteLines::new(io::Cursor::new(&input[..]));
+        let tree = BalancedParensTree::new(lines.into_depth_first_traversal());
+        let root = tree.root();
+
+        assert!(root.contains(b"ab"));
+        assert!(root.contains(b"bcd"));
+        assert!(!root.contains(b"bc"));
+        assert!(!root.contains(b"abcd"));
+        assert!(!root.contains(b""));
+
+        assert_eq!(root.longest_prefix_of(b"abcde"), Some(3));
+        assert_eq!(root.longest_prefix_of(b"abx"), Some(2));
+        assert_eq!(root.longest_prefix_of(b"bc"), Some(1));
+        assert_eq!(root.longest_prefix_of(b"x"), None);
+
+        assert_eq!(
+            root.common_prefix_search(b"abcde").collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert_eq!(root.common_prefix_search(b"").count(), 0);
+
+        let words = |prefix: &[u8]| {
+            tree.root()
+                .predictive_search(prefix)
+                .map(|w| String::from_utf8(w).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words(b"ab"), ["ab", "abc", "abd"]);
+        assert_eq!(words(b"bc"), ["bcd"]);
+        assert_eq!(words(b""), ["a", "ab", "abc", "abd", "b", "bcd"]);
+        assert!(words(b"abx").is_empty());
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        let root = tree.root();
+        assert!(!root.contains(b""));
+        assert_eq!(root.longest_prefix_of(b"a"), None);
+        assert_eq!(root.common_prefix_search(b"a").count(), 0);
+        assert_eq!(root.predictive_search(b"").count(), 0);
+
+        // A single word is found only as a whole.
+        let tree = DfudsTree::new(word_lines(&["abc"]));
+        let root = tree.root();
+        assert!(root.contains(b"abc"));
+        assert!(!root.contains(b"ab"));
+        assert_eq!(root.longest_prefix_of(b"ab"), None);
+        assert_eq!(root.longest_prefix_of(b"abc"), Some(3));
+        assert_eq!(
+            root.common_prefix_search(b"abcabc").collect::<Vec<_>>(),
+            [3]
+        );
+        assert_eq!(tree.root().predictive_search(b"abc").count(), 1);
+        assert_eq!(tree.root().predictive_search(b"abcd").count(), 0);
+    }
+
+    #[test]
+    fn word_id() {
+        let words = numeric_words(3000, 7);
+        let tree = BalancedParensTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new())
+            .build_all();
+
+        for (id, word) in words.iter().enumerate() {
+            assert_eq!(tree.word_id(word.as_bytes()), Some(id as u64));
+            assert_eq!(tree.word(id as u64).unwrap(), word.as_bytes());
+        }
+        assert_eq!(tree.word_id(b"1"), None);
+        assert_eq!(tree.word_id(b"x"), None);
+        assert_eq!(tree.word(words.len() as u64), None);
+
+        let tree =
+            BalancedParensTree::<_>::new_builder(word_lines::<&str>(&[]), Letters::<u8>::new())
+                .build_all();
+        assert_eq!(tree.word_id(b""), None);
+        assert_eq!(tree.word(0), None);
+        assert_eq!(tree.word(u64::MAX), None);
+
+        // "ab" is a prefix of "abc" and "abd" but not a word.
+        let words = ["a", "abc", "abd", "b"];
+        let tree = BalancedParensTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new())
+            .build_all();
+        for (id, word) in words.iter().enumerate() {
+            assert_eq!(tree.word_id(word.as_bytes()), Some(id as u64));
+            assert_eq!(tree.word(id as u64).unwrap(), word.as_bytes());
+        }
+        assert_eq!(tree.word_id(b""), None);
+        assert_eq!(tree.word_id(b"ab"), None);
+        assert_eq!(tree.word_id(b"abcd"), None);
+    }
+
+    #[test]
+    fn letters_with_wrong_value_count() {
+        let tree = || {
+            let lines = ByteLines::new(io::Cursor::new(&b"a\nab\nb"[..]));
+            BalancedParensTree::<_>::new_builder(
+                lines.into_depth_first_traversal(),
+                Letters::<u8>::new(),
+            )
+            .build_all()
+        };
+        for values in [vec![1, 2], vec![1, 2, 3, 4]] {
+            let e = tree().with_values::<u32>(values).err().unwrap();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        }
+        assert!(tree().with_values::<u32>(vec![1, 2, 3]).is_ok());
+    }
+
+    #[test]
+    fn letters_with_values() {
+        let words = numeric_words(3000, 7);
+        let values = words
+            .iter()
+            .map(|w| w.len() as u32 * 10)
+            .collect::<Vec<_>>();
+        let tree = BalancedParensTree::<_>::new_builder(word_lines(&words), Letters::<u8>::new())
+            .build_all()
+            .with_values(values.clone())
+            .unwrap();
+
+        for (word, value) in words.iter().zip(values.iter()) {
+            assert_eq!(tree.get(word.as_bytes()), Some(value));
+        }
+        assert_eq!(tree.get(b"1"), None);
+        assert_eq!(
+            tree.iter()
+                .map(|(w, v)| (String::from_utf8(w).unwrap(), *v))
+                .collect::<Vec<_>>(),
+            words.into_iter().zip(values).collect::<Vec<_>>()
+        );
+
+        let mut buf = Vec::new();
+        tree.labels().write_to(&mut buf).unwrap();
+        let read = LettersWithValues::<u8, u32>::read_from(&buf[..]).unwrap();
+        assert_eq!(read.values, tree.labels().values);
+
+        let aligned = serialize::aligned(&buf);
+        let view = LettersWithValues::<u8, u32, Borrowed>::from_bytes(
+            &mut serialize::aligned_bytes(&aligned),
+        )
+        .unwrap();
+        assert_eq!(view.values, &tree.labels().values[..]);
+
+        let mut labels = tree.labels().clone();
+        labels.values.pop();
+        buf.clear();
+        labels.write_to(&mut buf).unwrap();
+        let e = LettersWithValues::<u8, u32>::read_from(&buf[..]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let tree =
+            BalancedParensTree::<_>::new_builder(word_lines::<&str>(&[]), Letters::<u8>::new())
+                .build_all()
+                .with_values::<u32>(Vec::new())
+                .unwrap();
+        assert_eq!(tree.get(b""), None);
+        assert_eq!(tree.iter().count(), 0);
+
+        // The value of a word is not shared with its prefixes or extensions.
+        let tree =
+            BalancedParensTree::<_>::new_builder(word_lines(&["ab", "abcd"]), Letters::<u8>::new())
+                .build_all()
+                .with_values(vec![1u32, 2])
+                .unwrap();
+        assert_eq!(tree.get(b"a"), None);
+        assert_eq!(tree.get(b"ab"), Some(&1));
+        assert_eq!(tree.get(b"abc"), None);
+        assert_eq!(tree.get(b"abcd"), Some(&2));
+    }
+
+    #[test]
+    fn patricia() {
+        let lines = ByteLines::new(io::Cursor::new(b"aaa111222\nab\nabc3344\nd"));
+        let plain = BalancedParensTree::new(lines.into_depth_first_traversal());
+        let tree = BalancedParensTree::<_>::new_builder(
+            PatriciaTraversal::new(plain.root()),
+            PatriciaLetters::<u8>::new(),
+        )
+        .build_all();
+        let labels = (0..tree.len())
+            .map(|i| String::from_utf8(tree.labels().values(i).unwrap().to_vec()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["a", "aa111222", "b", "c3344", "d"]);
+
+        let words = numeric_words(3000, 7);
+        let plain = BalancedParensTree::new(word_lines(&words));
+        let tree = BalancedParensTree::<_>::new_builder(
+            PatriciaTraversal::new(plain.root()),
+            PatriciaLetters::<u8>::new(),
+        )
+        .build_all();
+        assert!(tree.len() < plain.len());
+        assert_eq!(
+            Words::new(tree.root())
+                .map(|w| String::from_utf8(w.concat()).unwrap())
+                .collect::<Vec<_>>(),
+            words
+        );
+
+        let mut buf = Vec::new();
+        tree.labels().write_to(&mut buf).unwrap();
+        let read = PatriciaLetters::<u8>::read_from(&buf[..]).unwrap();
+        assert_eq!(read.offsets, tree.labels().offsets);
+        let aligned = serialize::aligned(&buf);
+        let view =
+            PatriciaLetters::<u8, Borrowed>::from_bytes(&mut serialize::aligned_bytes(&aligned))
+                .unwrap();
+        assert_eq!(view.values(10), tree.labels().values(10));
+
+        let labels = |words: &[&str]| {
+            let plain = BalancedParensTree::new(word_lines(words));
+            let tree = BalancedParensTree::<_>::new_builder(
+                PatriciaTraversal::new(plain.root()),
+                PatriciaLetters::<u8>::new(),
+            )
+            .build_all();
+            (0..tree.len())
+                .map(|i| String::from_utf8(tree.labels().values(i).unwrap().to_vec()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert!(labels(&[]).is_empty());
+        assert_eq!(labels(&["abc"]), ["abc"]);
+        // A word end is not merged into its only child.
+        assert_eq!(labels(&["a", "abc"]), ["a", "bc"]);
+        assert_eq!(labels(&["abc", "abd"]), ["ab", "c", "d"]);
+    }
+
+    #[test]
+    fn range() {
+        let words = numeric_words(3000, 7);
+        let tree = BalancedParensTree::new(word_lines(&words));
+
+        let bounds = ["", "0", "1", "105", "1050", "1051", "2", "35", "9", "99999"];
+        for lo in bounds {
+            for hi in bounds {
+                let (lo, hi) = (lo.as_bytes(), hi.as_bytes());
+                let expected = words
+                    .iter()
+                    .filter(|w| lo <= w.as_bytes() && w.as_bytes() < hi)
+                    .map(|w| w.as_bytes().to_vec())
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    Words::range(tree.root(), lo..hi).collect::<Vec<_>>(),
+                    expected
+                );
+
+                let expected = words
+                    .iter()
+                    .filter(|w| lo < w.as_bytes() && w.as_bytes() <= hi)
+                    .map(|w| w.as_bytes().to_vec())
+                    .collect::<Vec<_>>();
+                let range = (Bound::Excluded(lo), Bound::Included(hi));
+                assert_eq!(
+                    Words::range::<_, &[u8]>(tree.root(), range).collect::<Vec<_>>(),
+                    expected
+                );
+            }
+        }
+        assert_eq!(
+            Words::range::<_, &[u8]>(tree.root(), ..).count(),
+            words.len()
+        );
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert_eq!(Words::range::<_, &[u8]>(tree.root(), ..).count(), 0);
+        assert_eq!(Words::range(tree.root(), &b""[..]..&b"z"[..]).count(), 0);
+
+        // Bounds outside of the words, and bounds that are prefixes of words.
+        let words = ["ab", "abc", "b", "bcd"];
+        let louds = LoudsTree::new(word_lines(&words));
+        let dfuds = DfudsTree::new(word_lines(&words));
+        let cases: [(&[u8], &[u8], &[&str]); 7] = [
+            (b"a", b"abc", &["ab"]),
+            (b"abc", b"ab", &[]),
+            (b"abd", b"bc", &["b"]),
+            (b"", b"a", &[]),
+            (b"bcd", b"bcd", &[]),
+            (b"bcda", b"z", &[]),
+            (b"\0", b"\xff", &words),
+        ];
+        for (lo, hi, expected) in cases {
+            let expected = expected.iter().map(|w| w.as_bytes()).collect::<Vec<_>>();
+            assert_eq!(
+                Words::range(louds.root(), lo..hi).collect::<Vec<_>>(),
+                expected
+            );
+            assert_eq!(
+                Words::range(dfuds.root(), lo..hi).collect::<Vec<_>>(),
+                expected
+            );
+        }
+        let range = (Bound::Excluded(&b"bcd"[..]), Bound::Unbounded);
+        assert_eq!(Words::range::<_, &[u8]>(dfuds.root(), range).count(), 0);
+        let range = (Bound::Unbounded, Bound::Excluded(&b"ab"[..]));
+        assert_eq!(Words::range::<_, &[u8]>(louds.root(), range).count(), 0);
+        assert_eq!(
+            Words::range(louds.root(), &b"bcd"[..]..=&b"bcd"[..]).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn words_rev() {
+        let mut words = numeric_words(3000, 7);
+        let tree = BalancedParensTree::new(word_lines(&words));
+
+        words.reverse();
+        assert_eq!(
+            WordsRev::new(tree.root())
+                .map(|w| String::from_utf8(w).unwrap())
+                .collect::<Vec<_>>(),
+            words
+        );
+        assert_eq!(
+            WordsRev::new(tree.root())
+                .take(3)
+                .map(|w| String::from_utf8(w).unwrap())
+                .collect::<Vec<_>>(),
+            ["9996", "9989", "9982"]
+        );
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert_eq!(WordsRev::new(tree.root()).count(), 0);
+
+        // A word comes after the words it prefixes, in reverse order.
+        let tree = BalancedParensTree::new(word_lines(&["a", "ab", "abc", "b"]));
+        assert_eq!(
+            WordsRev::new(tree.root())
+                .map(|w| String::from_utf8(w).unwrap())
+                .collect::<Vec<_>>(),
+            ["b", "abc", "ab", "a"]
+        );
+
+        let tree = BalancedParensTree::new(word_lines(&["x"]));
+        assert_eq!(WordsRev::new(tree.root()).collect::<Vec<_>>(), [b"x"]);
+    }
+
+    #[test]
+    fn levenshtein_search() {
+        let words = numeric_words(3000, 7);
+        let tree = BalancedParensTree::new(word_lines(&words));
+
+        fn distance(a: &[u8], b: &[u8]) -> usize {
+            let mut row = (0..=b.len()).collect::<Vec<_>>();
+            for (i, x) in a.iter().enumerate() {
+                let mut next = vec![i + 1];
+                for (j, y) in b.iter().enumerate() {
+                    next.push(
+                        (row[j] + usize::from(x != y))
+                            .min(row[j + 1] + 1)
+                            .min(next[j] + 1),
+                    );
+                }
+                row = next;
+            }
+            row[b.len()]
+        }
+        for query in ["", "7", "140", "1234", "99999", "abc"] {
+            for max_distance in 0..3 {
+                let expected = words
+                    .iter()
+                    .map(|w| {
+                        (
+                            w.as_bytes().to_vec(),
+                            distance(w.as_bytes(), query.as_bytes()),
+                        )
+                    })
+                    .filter(|(_, d)| *d <= max_distance)
+                    .collect::<Vec<_>>();
+                let actual = tree
+                    .root()
+                    .levenshtein_search(query.as_bytes(), max_distance)
+                    .collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+            }
+        }
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert_eq!(tree.root().levenshtein_search(b"", 2).count(), 0);
+
+        let tree = BalancedParensTree::new(word_lines(&["a", "ab", "ba"]));
+        assert_eq!(tree.root().levenshtein_search(b"", 0).count(), 0);
+        assert_eq!(
+            tree.root().levenshtein_search(b"", 1).collect::<Vec<_>>(),
+            [(b"a".to_vec(), 1)]
+        );
+        assert_eq!(
+            tree.root().levenshtein_search(b"b", 1).collect::<Vec<_>>(),
+            [(b"a".to_vec(), 1), (b"ab".to_vec(), 1), (b"ba".to_vec(), 1)]
+        );
+
+        // A distance above every word length matches all of the words.
+        assert_eq!(
+            tree.root()
+                .levenshtein_search(b"xyz", 10)
+                .collect::<Vec<_>>(),
+            [(b"a".to_vec(), 3), (b"ab".to_vec(), 3), (b"ba".to_vec(), 3)]
+        );
+    }
+
+    #[test]
+    fn automaton_search() {
+        // Accepts the words that match a glob pattern consisting of literals and `*`.
+        struct Glob(Vec<u8>);
+        impl Automaton<u8> for Glob {
+            type State = Vec<usize>; // The positions of the pattern that can be reached.
+            fn start(&self) -> Self::State {
+                self.close(vec![0])
+            }
+            fn is_match(&self, state: &Self::State) -> bool {
+                state.contains(&self.0.len())
+            }
+            fn can_match(&self, state: &Self::State) -> bool {
+                !state.is_empty()
+            }
+            fn accept(&self, state: &Self::State, value: &u8) -> Self::State {
+                let next = state
+                    .iter()
+                    .filter_map(|&i| match self.0.get(i) {
+                        Some(b'*') => Some(i),
+                        Some(c) if c == value => Some(i + 1),
+                        _ => None,
+                    })
+                    .collect();
+                self.close(next)
+            }
+        }
+        impl Glob {
+            fn close(&self, mut state: Vec<usize>) -> Vec<usize> {
+                let mut i = 0;
+                while i < state.len() {
+                    if self.0.get(state[i]) == Some(&b'*') && !state.contains(&(state[i] + 1)) {
+                        state.push(state[i] + 1);
+                    }
+                    i += 1;
+                }
+                state
+            }
+        }
+
+        let words = numeric_words(3000, 7);
+        let tree = BalancedParensTree::new(word_lines(&words));
+
+        let matches = |pattern: &str| {
+            tree.root()
+                .search(Glob(pattern.as_bytes().to_vec()))
+                .map(|w| String::from_utf8(w).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let expected = words
+            .iter()
+            .filter(|w| w.starts_with('1') && w.ends_with('0'))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(matches("1*0"), expected);
+        assert_eq!(matches("*"), words);
+        assert_eq!(matches("7"), ["7"]);
+        assert!(matches("x*").is_empty());
+
+        // A subtree is not visited once the automaton cannot match.
+        struct Counted<'a>(Glob, &'a Cell<usize>);
+        impl Automaton<u8> for Counted<'_> {
+            type State = Vec<usize>;
+            fn start(&self) -> Self::State {
+                self.0.start()
+            }
+            fn is_match(&self, state: &Self::State) -> bool {
+                self.0.is_match(state)
+            }
+            fn can_match(&self, state: &Self::State) -> bool {
+                self.0.can_match(state)
+            }
+            fn accept(&self, state: &Self::State, value: &u8) -> Self::State {
+                self.1.set(self.1.get() + 1);
+                self.0.accept(state, value)
+            }
+        }
+        let accepted = Cell::new(0);
+        let mut search = tree.root().search(Counted(Glob(b"14".to_vec()), &accepted));
+        assert_eq!(search.next_with_state(), Some((b"14".to_vec(), vec![2])));
+        assert_eq!(search.next(), None);
+        assert!(accepted.get() <= 30, "{}", accepted.get());
+
+        let tree = BalancedParensTree::new(word_lines::<&str>(&[]));
+        assert_eq!(tree.root().search(Glob(b"*".to_vec())).count(), 0);
+
+        // The empty word is never yielded, even if the automaton matches it.
+        let tree = BalancedParensTree::new(word_lines(&["a", "ab", "b"]));
+        let matches = |pattern: &str| {
+            tree.root()
+                .search(Glob(pattern.as_bytes().to_vec()))
+                .map(|w| String::from_utf8(w).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert!(matches("").is_empty());
+        assert_eq!(matches("*"), ["a", "ab", "b"]);
+        assert_eq!(matches("a*"), ["a", "ab"]);
+        assert_eq!(matches("*b"), ["ab", "b"]);
+    }
+}